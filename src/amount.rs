@@ -0,0 +1,124 @@
+//! Exact decimal token amounts given on the command line
+
+use std::fmt;
+use std::str::FromStr;
+
+/// An amount of tokens in the highest denomination, such as "1.5", kept as decimal digits so that
+/// it converts to the smallest unit without losing precision.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenAmount {
+    integer: String,
+    fraction: String,
+}
+
+impl TokenAmount {
+    /// Converts the amount to the smallest unit of a token with `decimals` decimals.
+    pub fn to_units(&self, decimals: u32) -> Result<u128, String> {
+        let decimals = decimals as usize;
+        if self.fraction.len() > decimals {
+            return Err(format!("{self} has more than {decimals} decimals"));
+        }
+        format!("{}{:0<decimals$}", self.integer, self.fraction)
+            .parse()
+            .map_err(|_| format!("{self} is too large"))
+    }
+}
+
+impl FromStr for TokenAmount {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (integer, fraction) = s.trim().split_once('.').unwrap_or((s.trim(), ""));
+        let is_digits = |part: &str| part.bytes().all(|b| b.is_ascii_digit());
+        if integer.is_empty() && fraction.is_empty() || !is_digits(integer) || !is_digits(fraction)
+        {
+            return Err(format!("invalid amount `{s}`, expected a decimal number"));
+        }
+
+        let integer = integer.trim_start_matches('0');
+        Ok(TokenAmount {
+            integer: if integer.is_empty() { "0" } else { integer }.to_string(),
+            fraction: fraction.trim_end_matches('0').to_string(),
+        })
+    }
+}
+
+impl fmt::Display for TokenAmount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.integer)?;
+        if !self.fraction.is_empty() {
+            write!(f, ".{}", self.fraction)?;
+        }
+        Ok(())
+    }
+}
+
+/// Formats `units` of a token with `decimals` decimals in the highest denomination.
+pub fn format_units(units: u128, decimals: u32) -> String {
+    let digits = format!("{units:0>width$}", width = decimals as usize + 1);
+    let (integer, fraction) = digits.split_at(digits.len() - decimals as usize);
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.is_empty() {
+        integer.to_string()
+    } else {
+        format!("{integer}.{fraction}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn units(amount: &str) -> Result<u128, String> {
+        amount.parse::<TokenAmount>()?.to_units(11)
+    }
+
+    #[test]
+    fn parses_decimal_amounts() {
+        assert_eq!(units("1.5"), Ok(150_000_000_000));
+        assert_eq!(units(".5"), Ok(50_000_000_000));
+        assert_eq!(units("1."), Ok(100_000_000_000));
+        assert_eq!(units("007.250"), Ok(725_000_000_000));
+        assert_eq!(units("0.00000000001"), Ok(1));
+    }
+
+    #[test]
+    fn rejects_invalid_amounts() {
+        for amount in [".", "", "1.2.3", "-1", "1e5", "1,5"] {
+            assert!(amount.parse::<TokenAmount>().is_err(), "{amount}");
+        }
+    }
+
+    #[test]
+    fn rejects_too_many_decimals() {
+        assert_eq!(
+            units("0.000000000001"),
+            Err("0.000000000001 has more than 11 decimals".to_string())
+        );
+        // Trailing zeros do not count.
+        assert_eq!(units("0.000000000010"), Ok(1));
+    }
+
+    #[test]
+    fn rejects_overflowing_amounts() {
+        assert_eq!(
+            units("3402823669209384634633746074.31768211455"),
+            Ok(u128::MAX)
+        );
+        assert_eq!(
+            units("3402823669209384634633746074.31768211456"),
+            Err("3402823669209384634633746074.31768211456 is too large".to_string())
+        );
+    }
+
+    #[test]
+    fn formats_units() {
+        assert_eq!(format_units(0, 11), "0");
+        assert_eq!(format_units(1, 11), "0.00000000001");
+        assert_eq!(format_units(150_000_000_000, 11), "1.5");
+        assert_eq!(format_units(1_500, 0), "1500");
+        for value in [0, 1, 150_000_000_000, 123_456_789_012_345, u128::MAX] {
+            assert_eq!(units(&format_units(value, 11)), Ok(value));
+        }
+    }
+}
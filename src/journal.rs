@@ -0,0 +1,212 @@
+//! A local record of the bulk runs of `create-pots` and `register-users`, kept so that an
+//! interrupted run can be resumed
+//!
+//! The journal is a file of JSON lines, each one telling what became of a planned unit of work,
+//! a pot or a range of users, with the transaction sending it. The last line about a unit is its
+//! current status.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::future::Future;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use subxt::backend::legacy::rpc_methods::Bytes;
+use subxt::backend::rpc::{rpc_params, RpcClient};
+use subxt::config::{Config, Hasher};
+use subxt::utils::H256;
+
+use crate::nodle::NodleConfig;
+
+/// A unit of work sent in a single transaction.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Unit {
+    /// The pot with this id.
+    Pot { id: u32 },
+    /// The users with ids from `first` to `last` included, registered in `pot_id`. Their ids are
+    /// the indexes their addresses are derived from, or their positions in the address file.
+    Users { pot_id: u32, first: u32, last: u32 },
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Unit::Pot { id } => write!(f, "pot {id}"),
+            Unit::Users {
+                pot_id,
+                first,
+                last,
+            } => write!(f, "users {first} to {last} of pot {pot_id}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Status {
+    /// About to be sent.
+    Planned,
+    /// Sent, but not known to be included yet.
+    Sent,
+    /// Executed in a block that may still be retracted.
+    InBlock,
+    /// Executed in a finalized block.
+    Finalized,
+    /// Found done on chain when resuming, without knowing the transaction that did it.
+    Found,
+    /// Not sent, or not executed successfully.
+    Failed,
+}
+
+/// What became of a unit, as written on a line of the journal.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub unit: Unit,
+    pub status: Status,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tx_hash: Option<H256>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub block_hash: Option<H256>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Entry {
+    pub fn new(unit: Unit, status: Status) -> Self {
+        Entry {
+            unit,
+            status,
+            tx_hash: None,
+            nonce: None,
+            block_hash: None,
+            error: None,
+        }
+    }
+
+    /// The unit with `status`, sent in the transaction `tx_hash` with `nonce`.
+    pub fn sent(unit: Unit, status: Status, tx_hash: H256, nonce: u64) -> Self {
+        Entry {
+            tx_hash: Some(tx_hash),
+            nonce: Some(nonce),
+            ..Entry::new(unit, status)
+        }
+    }
+}
+
+/// The journal file opened for appending, along with the last status of each unit.
+pub struct Journal {
+    file: File,
+    entries: HashMap<Unit, Entry>,
+    /// The hashes of the transactions waiting in the pool of the node when resuming.
+    pool: HashSet<H256>,
+}
+
+impl Journal {
+    /// Starts a new journal at `path`, which must not exist yet.
+    pub fn create(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let file = OpenOptions::new()
+            .append(true)
+            .create_new(true)
+            .open(path)
+            .map_err(|e| format!("cannot create the journal {}: {e}", path.display()))?;
+        Ok(Journal {
+            file,
+            entries: HashMap::new(),
+            pool: HashSet::new(),
+        })
+    }
+
+    /// Reads the journal at `path` to resume its run, appending to it from then on. The
+    /// transactions waiting in the pool of the node behind `rpc_client` are read too.
+    pub async fn resume(
+        path: &Path,
+        rpc_client: &RpcClient,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut entries = HashMap::new();
+        let reader = BufReader::new(File::open(path)?);
+        for (number, line) in reader.lines().enumerate() {
+            let line = line?;
+            // The last line may be cut short if the run was killed while writing it.
+            let Ok(entry) = serde_json::from_str::<Entry>(&line) else {
+                println!("skipping unreadable line {} of the journal", number + 1);
+                continue;
+            };
+            entries.insert(entry.unit.clone(), entry);
+        }
+        if entries.is_empty() {
+            return Err(format!("the journal {} records nothing", path.display()).into());
+        }
+
+        let pool = rpc_client
+            .request::<Vec<Bytes>>("author_pendingExtrinsics", rpc_params![])
+            .await?
+            .into_iter()
+            .map(|extrinsic| <NodleConfig as Config>::Hasher::hash(&extrinsic.0))
+            .collect();
+
+        let file = OpenOptions::new().append(true).open(path)?;
+        Ok(Journal {
+            file,
+            entries,
+            pool,
+        })
+    }
+
+    /// Appends `entry` to the journal, making it the status of its unit.
+    pub fn record(&mut self, entry: Entry) -> std::io::Result<()> {
+        writeln!(self.file, "{}", serde_json::to_string(&entry)?)?;
+        self.entries.insert(entry.unit.clone(), entry);
+        Ok(())
+    }
+
+    /// Tells whether `unit` still has to be sent, which is the case unless the journal shows it
+    /// went through or `done`, only checked for units the journal knows of, finds it done on
+    /// chain. Fails as [`Journal::ensure_not_in_pool`] does otherwise.
+    pub async fn needs_sending(
+        &mut self,
+        unit: &Unit,
+        done: impl Future<Output = Result<bool, subxt::Error>>,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        let Some(entry) = self.entries.get(unit) else {
+            return Ok(true);
+        };
+        if matches!(entry.status, Status::Finalized | Status::Found) {
+            return Ok(false);
+        }
+        if done.await? {
+            self.found(unit)?;
+            return Ok(false);
+        }
+        self.ensure_not_in_pool(unit)?;
+        Ok(true)
+    }
+
+    /// Records that `unit` was found done on chain, unless the journal already shows it went
+    /// through or does not know of it.
+    pub fn found(&mut self, unit: &Unit) -> std::io::Result<()> {
+        match self.entries.get(unit) {
+            Some(entry) if !matches!(entry.status, Status::Finalized | Status::Found) => {
+                self.record(Entry::new(unit.clone(), Status::Found))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Fails if the transaction last sending `unit` is still waiting in the pool, since sending
+    /// the unit again could then do it twice.
+    pub fn ensure_not_in_pool(&self, unit: &Unit) -> Result<(), String> {
+        let tx_hash = self.entries.get(unit).and_then(|entry| entry.tx_hash);
+        match tx_hash.filter(|tx_hash| self.pool.contains(tx_hash)) {
+            Some(tx_hash) => Err(format!(
+                "the transaction {tx_hash:?} sending {unit} is still waiting in the pool, resume \
+                 once it is included or has expired"
+            )),
+            None => Ok(()),
+        }
+    }
+}
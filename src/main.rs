@@ -1,39 +1,134 @@
 use clap::{Parser, Subcommand};
-use codec::Encode;
-use std::collections::VecDeque;
-use std::str::FromStr;
+use std::path::PathBuf;
 use subxt::{
     backend::{legacy::LegacyRpcMethods, rpc::RpcClient},
+    utils::AccountId32,
     OnlineClient,
 };
-use subxt_signer::{sr25519, SecretUri};
-const MAX_USERS_ONE_BLOCK: usize = 500;
 
+mod amount;
+mod journal;
+mod multisig;
 mod nodle;
-
+mod offline;
+mod payment;
+mod relay;
+mod signer;
+mod sponsorship;
+mod tc;
+mod tx;
+mod users;
+mod xcm;
+
+// The commands sending transactions, which can also be built for offline signing.
 #[derive(Debug, Subcommand)]
-enum Commands {
+enum TxCommand {
     /// Proposes an xcm as a technical committee member for a native transaction on the relay chain
-    #[command(arg_required_else_help = true)]
+    /// or a system parachain
+    #[command(arg_required_else_help = true, subcommand_negates_reqs = true)]
     ProposeXcm {
         /// A string containing a native transaction on relay-chain encoded in hex.
         ///
         /// Example: "4603ea070000d0070000" for registering swap between para 2026 and para 2000
+        ///
+        /// This is required unless one of the call templates is used instead. It can be repeated
+        /// to execute several transactions with a single proposal, in which case the template call
+        /// comes first.
+        #[arg(short, long, required = true)]
+        transact: Vec<String>,
+        /// "Dry Run" the proposal. This will output the proposal to be sent to the chain without
+        /// actually doing so.
+        #[arg(short, long, global = true)]
+        dry_run: bool,
+        /// The maximum number of tokens we are willing to spend on fees.
+        ///
+        /// This is an exact decimal number, and is interpreted as the number of tokens in the
+        /// highest denomination. For example, if the token has 18 decimals, then the default value
+        /// of 1 means 1 token.
+        #[arg(short, long, global = true, default_value = "1")]
+        fee_limit: TokenAmount,
+        /// Estimates the fee from the cost of executing the xcm on the destination rather than
+        /// spending up to `--fee-limit`, which then only bounds the estimate.
+        #[arg(long, global = true)]
+        auto_fee: bool,
+        /// The safety margin in percent added on top of the fee estimated by `--auto-fee`.
+        #[arg(long, global = true, default_value_t = 20)]
+        fee_margin: u128,
+        /// The chain the xcm is sent to and where the transaction is executed.
+        ///
+        /// One of "relay", "asset-hub", "collectives", "bridge-hub" or "sibling:<para_id>" for any
+        /// other parachain connected to the same relay chain.
+        #[arg(long, global = true, default_value = "relay")]
+        dest: Destination,
+        /// The para id receiving the surplus fees once the transaction is executed.
+        ///
+        /// If not provided, the para id of the connected chain is read from `ParachainInfo`.
+        #[arg(long, global = true)]
+        beneficiary_para: Option<u32>,
+        /// The weight to reserve for each transact instruction as "ref_time,proof_size".
+        ///
+        /// If not provided, the weight of each transaction is queried from `--relay-url` and
+        /// increased by `--weight-margin`.
+        #[arg(long, global = true)]
+        weight: Option<TransactWeight>,
+        /// The safety margin in percent added on top of the weight queried from `--relay-url`.
+        #[arg(long, global = true, default_value_t = 20)]
+        weight_margin: u64,
+        /// The xcm version used for building the message.
+        ///
+        /// If not provided, the version the destination advertised to our parachain through
+        /// `PolkadotXcm::SupportedVersion` is used.
+        ///
+        /// Only v2 and v3 can be built, since the metadata of our runtime has no v4 types. A
+        /// destination advertising v4 or newer gets a v3 message, which it still accepts.
+        #[arg(long, global = true, value_enum)]
+        xcm_version: Option<XcmVersion>,
+        /// The number of committee members that need to approve the proposal.
+        ///
+        /// One of "simple-majority", "two-thirds", "unanimous" or an explicit number of members.
+        #[arg(long, global = true, default_value_t = Threshold::SimpleMajority)]
+        threshold: Threshold,
+        /// How several transactions are combined in a single xcm.
+        ///
+        /// "batch-all" wraps them in a single `Utility::batch_all` so that they all succeed or fail
+        /// together, while "instructions" executes each one in its own transact instruction.
+        #[arg(long, global = true, value_enum, default_value_t = Batch::BatchAll)]
+        batch: Batch,
+        /// Builds the relay chain transaction from a template instead of `--transact`.
+        #[command(subcommand)]
+        call: Option<RelayCall>,
+    },
+    /// Proposes a root call on our parachain as a technical committee member
+    #[command(arg_required_else_help = true)]
+    ProposeLocal {
+        /// The call to propose, either scale encoded in hex or as JSON.
+        ///
+        /// The JSON form names the pallet, the call and its arguments, for example:
+        /// '{"pallet": "CollatorSelection", "call": "set_desired_candidates", "args": {"max": 5}}'
         #[arg(short, long)]
-        transact: String,
+        call: String,
         /// "Dry Run" the proposal. This will output the proposal to be sent to the chain without
         /// actually doing so.
         #[arg(short, long)]
         dry_run: bool,
-        /// The maximum number of tokens we are willing to spend on fees.
+        /// The number of committee members that need to approve the proposal.
         ///
-        /// This is a float number, and is interpreted as the number of tokens in the highest
-        /// denomination. For example, if the token has 18 decimals, then the default value of 1 means
-        /// 1 token.
-        #[arg(short, long, default_value = "1")]
-        fee_limit: f32,
+        /// One of "simple-majority", "two-thirds", "unanimous" or an explicit number of members.
+        #[arg(long, default_value_t = Threshold::SimpleMajority)]
+        threshold: Threshold,
+    },
+    /// Lists, votes on and closes technical committee proposals
+    Tc {
+        #[command(subcommand)]
+        command: TcCommand,
     },
-    /// Creates a number of sponsorship pots with their ids starting from 0 and incrementing
+    /// Lists, approves and cancels the pending calls of the `--multisig` account
+    Multisig {
+        #[command(subcommand)]
+        command: MultisigCommand,
+    },
+    /// Creates a number of sponsorship pots with their ids starting from 0 and incrementing,
+    /// skipping the ids already in use
     CreatePots {
         /// The number of pots to create.
         #[arg(short, long, default_value_t = 1)]
@@ -41,22 +136,104 @@ enum Commands {
         /// The starting id of the pots. The ids will be incremented from this value.
         #[arg(short, long, default_value_t = 0)]
         starting_id: u32,
+        /// The calls the users of the pots can have sponsored.
+        ///
+        /// "any-safe" covers any call that cannot move funds out of the proxy accounts of the
+        /// users, while "uniques" only covers the calls of `Uniques`.
+        #[arg(long, value_enum, default_value_t = SponsorshipKind::AnySafe)]
+        sponsorship_type: SponsorshipKind,
+        /// The most each pot pays in fees for all of its users together, in NODL.
+        ///
+        /// This is an exact decimal number, such as 123 or 0.5.
+        #[arg(long, default_value = "123")]
+        fee_quota: TokenAmount,
+        /// The most each pot reserves for all of its users together, in NODL.
+        ///
+        /// This is an exact decimal number, such as 9 or 0.5.
+        #[arg(long, default_value = "9")]
+        reserve_quota: TokenAmount,
+        /// Updates the limits and sponsorship type of the pots that already exist to those of the
+        /// new pots where they differ, rather than leaving them as they are.
+        ///
+        /// Only the pots sponsored by the account sending the transactions can be updated.
+        #[arg(long)]
+        update_existing: bool,
     },
-    /// Registers a number of users for the specified sponsorship pot
+    /// Registers users for the specified sponsorship pot, either read from a file or derived from
+    /// //Alice for load testing
     RegisterUsers {
         /// The pot to register users in.
         #[arg(short, long, default_value_t = 0)]
         pot_id: u32,
-        /// The number of users to add with their addresses derived form //Alice
-        #[arg(short = 'n', long, default_value_t = 1)]
-        users: u32,
-        /// The starting id of the users. The ids will be incremented from this value.
-        /// The user address is dervied from //Alice/{id}
+        #[command(flatten)]
+        users: UserArgs,
+        #[command(flatten)]
+        export: ExportArgs,
+        /// The most the pot pays in fees for each user, in NODL.
+        ///
+        /// This is an exact decimal number, such as 43 or 0.5.
+        #[arg(long, default_value = "43")]
+        fee_quota: TokenAmount,
+        /// The most the pot reserves for each user, in NODL.
+        ///
+        /// This is an exact decimal number, such as 7 or 0.5.
+        #[arg(long, default_value = "7")]
+        reserve_quota: TokenAmount,
+    },
+    /// Removes users from the specified sponsorship pot, taking them from the same sources as
+    /// `register-users`
+    RemoveUsers {
+        /// The pot to remove users from.
+        #[arg(short, long, default_value_t = 0)]
+        pot_id: u32,
+        #[command(flatten)]
+        users: UserArgs,
+    },
+    /// Removes a number of sponsorship pots with their ids starting from 0 and incrementing,
+    /// once they have no users left
+    RemovePots {
+        /// The number of pots to remove.
+        #[arg(short, long, default_value_t = 1)]
+        pots: u32,
+        /// The starting id of the pots. The ids will be incremented from this value.
         #[arg(short, long, default_value_t = 0)]
         starting_id: u32,
     },
 }
 
+#[derive(Debug, Subcommand)]
+enum Commands {
+    #[command(flatten)]
+    Tx(TxCommand),
+    /// Composes the transactions of another command without signing them, writing them to a file
+    /// to be signed offline with `sign`
+    Build {
+        /// The SS58 address of the account signing the transactions offline.
+        #[arg(short, long)]
+        account: AccountId32,
+        /// The file the unsigned transactions are written to.
+        #[arg(short, long)]
+        out: PathBuf,
+        #[command(subcommand)]
+        command: TxCommand,
+    },
+    /// Signs the transactions written by `build` with `--signer`, without connecting to any node
+    Sign {
+        /// The file written by `build`.
+        #[arg(short, long)]
+        input: PathBuf,
+        /// The file the signed transactions are written to.
+        #[arg(short, long)]
+        out: PathBuf,
+    },
+    /// Submits the transactions signed by `sign` and waits for them to be finalized
+    Submit {
+        /// The file written by `sign`.
+        #[arg(short, long)]
+        input: PathBuf,
+    },
+}
+
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
@@ -66,273 +243,402 @@ struct Args {
     #[arg(short, long, default_value = "ws://localhost:9280")]
     url: String,
 
-    /// The secret uri to the private key for the signer of the transactions.
-    ///
-    /// Here is the expected format for the secret uri:
-    ///
-    /// phrase/path0/path1///password
-    ///
-    /// 111111 22222 22222   33333333
-    ///
-    /// Where:
-    ///
-    /// 1s denotes a phrase or hex string. If this is not provided, the DEV_PHRASE is used instead.
-    ///
-    /// 2s denote optional "derivation junctions" which are used to derive keys. Each of these is
-    /// separated by "/". A derivation junction beginning with "/" (ie "//" in the original string)
-    /// is a "hard" path.
-    ///
-    /// 3s denotes an optional password which is used in conjunction with the phrase provided in 1
-    /// to generate an initial key. If hex is provided for 1, it's ignored.
+    /// RPC endpoint of the chain receiving the xcm sent by `propose-xcm`, which is the relay chain
+    /// unless `--dest` says otherwise. It is used to decode the transaction and estimate its
+    /// weight.
+    #[arg(long)]
+    relay_url: Option<String>,
+
+    /// Path to a `.scale` metadata file of the chain receiving the xcm sent by `propose-xcm`.
     ///
-    /// Notes:
+    /// This is used for decoding the transaction when `--relay-url` is not provided. Without
+    /// either, `--transact` calls are proposed as they are without being decoded, while
+    /// templates and batches of several calls cannot be encoded.
+    #[arg(long)]
+    relay_metadata: Option<PathBuf>,
+
+    /// The number of decimals of the relay chain token, used for converting token amounts when
+    /// `--relay-url` is not provided.
+    #[arg(long)]
+    relay_decimals: Option<u32>,
+
+    #[command(flatten)]
+    signer: SignerArgs,
+
+    #[command(flatten)]
+    tx: TxArgs,
+
+    /// Sends the transactions on behalf of a multisig account the signer is a signatory of, given
+    /// as "signatory,signatory,...;threshold" with SS58 addresses.
     ///
-    /// If 1 is a 0x prefixed 64-digit hex string, then we'll interpret it as hex, and treat the hex
-    /// bytes as a seed/MiniSecretKey directly, ignoring any password.
+    /// Each call is wrapped in `Multisig::as_multi`, which opens it or approves it if another
+    /// signatory already did.
+    #[arg(long)]
+    multisig: Option<MultisigAccount>,
+
+    /// Records what becomes of each pot or chunk of users sent by `create-pots` or
+    /// `register-users` in this file, which must not exist yet, so that the run can be resumed
+    /// with `--resume` if it is interrupted.
     ///
-    /// Else if the phrase part is a valid BIP-39 phrase, we'll use the phrase (and password, if
-    /// provided) to generate a seed/MiniSecretKey.
+    /// Not available with a `--multisig` threshold above 1, since the calls then only get
+    /// approved rather than executed.
+    #[arg(long, conflicts_with = "resume")]
+    journal: Option<PathBuf>,
+
+    /// Resumes a `create-pots` or `register-users` run from the file it recorded with
+    /// `--journal`, with the same arguments, and keeps recording to it.
     ///
-    /// Uris like "//Alice" correspond to keys derived from a DEV_PHRASE, since no phrase part is
-    /// given.
-    #[arg(short, long, default_value = "//Alice")]
-    signer: String,
+    /// The units the journal shows finalized are skipped. The others are only sent again if they
+    /// are not done on chain already.
+    #[arg(long)]
+    resume: Option<PathBuf>,
 
     #[command(subcommand)]
     command: Commands,
 }
 
-#[subxt::subxt(runtime_metadata_path = "eden.scale")]
+#[subxt::subxt(
+    runtime_metadata_path = "eden.scale",
+    derive_for_type(
+        path = "runtime_eden::pallets_util::SponsorshipType",
+        derive = "Clone, PartialEq, Eq"
+    )
+)]
 pub mod eden {}
 
-use eden::runtime_types::{
-    pallet_mandate::pallet::Call::apply,
-    pallet_xcm::pallet::Call::send,
-    runtime_eden::{pallets_util::SponsorshipType, RuntimeCall},
-    sp_weights::weight_v2::Weight,
-    xcm::{
-        double_encoded::DoubleEncoded,
-        v2::OriginKind,
-        v3::{
-            junction::Junction,
-            junctions::Junctions,
-            multiasset::{
-                AssetId, Fungibility, MultiAsset, MultiAssetFilter, MultiAssets, WildMultiAsset,
-            },
-            multilocation::MultiLocation,
-            Instruction::{BuyExecution, DepositAsset, RefundSurplus, Transact, WithdrawAsset},
-            WeightLimit, Xcm,
-        },
-        VersionedMultiLocation, VersionedXcm,
-    },
-};
-
-const DOT_DECIMALS: u128 = 10_000_000_000; // 10 decimals
-const NODL_DECIMALS: u128 = 100_000_000_000; // 11 decimals
-
-fn build_fee_asset(amount: u128) -> MultiAsset {
-    MultiAsset {
-        id: AssetId::Concrete(MultiLocation {
-            parents: 0,
-            interior: Junctions::Here,
-        }),
-        fun: Fungibility::Fungible(amount),
-    }
-}
+use amount::{format_units, TokenAmount};
+use eden::runtime_types::{pallet_xcm::pallet::Call::send, runtime_eden::RuntimeCall};
+use journal::Journal;
+use multisig::{MultisigAccount, MultisigCommand};
+use relay::RelayCall;
+use signer::SignerArgs;
+use sponsorship::{PotLimits, SponsorshipKind};
+use tc::{TcCommand, Threshold};
+use tx::TxArgs;
+use users::{DefaultLimits, ExportArgs, UserArgs};
+use xcm::{Batch, Destination, TransactCall, TransactProgram, TransactWeight, XcmVersion};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = Args::parse();
 
+    if let Commands::Sign { input, out } = &args.command {
+        return offline::sign(input, out, &args.signer.keypair()?);
+    }
+
+    if let Commands::Build {
+        command:
+            TxCommand::Tc {
+                command: TcCommand::Proposals,
+            }
+            | TxCommand::Multisig {
+                command: MultisigCommand::List,
+            },
+        ..
+    } = &args.command
+    {
+        let error =
+            "tc proposals and multisig list only read the chain, so there is nothing to build";
+        return Err(error.into());
+    }
+
     let rpc_client = RpcClient::from_url(args.url.clone()).await?;
     let rpc = LegacyRpcMethods::<nodle::NodleConfig>::new(rpc_client.clone());
-    let api = OnlineClient::<nodle::NodleConfig>::from_rpc_client(rpc_client).await?;
-    let from = sr25519::Keypair::from_uri(&SecretUri::from_str(&args.signer)?)?;
+    let api = OnlineClient::<nodle::NodleConfig>::from_rpc_client(rpc_client.clone()).await?;
+
+    let building = matches!(args.command, Commands::Build { .. });
+    let (mut sender, command) = match args.command {
+        Commands::Tx(command) => {
+            let from = args.signer.lazy(api.genesis_hash())?;
+            (
+                tx::Sender::new(api.clone(), &rpc, args.tx.clone(), from).await?,
+                command,
+            )
+        }
+        Commands::Build {
+            account,
+            out,
+            command,
+        } => (
+            tx::Sender::build(api.clone(), &rpc, args.tx.clone(), account, out).await?,
+            command,
+        ),
+        Commands::Sign { .. } => unreachable!("signing does not need a connection"),
+        Commands::Submit { input } => return offline::submit(&api, &input).await,
+    };
+    println!("Connection Established nonce = {}", sender.nonce());
+    // The multisig commands already act on behalf of the multisig, so their calls are not wrapped.
+    if let Some(multisig) = &args.multisig {
+        if !matches!(command, TxCommand::Multisig { .. }) {
+            sender.set_multisig(multisig.clone())?;
+        }
+    }
 
-    let mut nonce = rpc
-        .system_account_next_index(&from.public_key().into())
-        .await?;
-    println!("Connection Established nonce = {nonce}");
+    let mut journal = match (&args.journal, &args.resume) {
+        (None, None) => None,
+        _ if building => return Err("nothing is sent with build, so there is no journal".into()),
+        _ if !matches!(
+            command,
+            TxCommand::CreatePots { .. } | TxCommand::RegisterUsers { .. }
+        ) =>
+        {
+            return Err("only create-pots and register-users keep a journal".into());
+        }
+        // A call approved by the first signatories is executed only once enough of them approve
+        // it, which the journal would otherwise take for done.
+        _ if args
+            .multisig
+            .as_ref()
+            .is_some_and(|multisig| multisig.threshold() > 1) =>
+        {
+            return Err(
+                "the calls of a multisig with a threshold above 1 are only approved, so they \
+                 cannot be journaled"
+                    .into(),
+            );
+        }
+        (Some(path), _) => Some(Journal::create(path)?),
+        (None, Some(path)) => Some(Journal::resume(path, &rpc_client).await?),
+    };
 
-    match args.command {
-        Commands::ProposeXcm {
+    match command {
+        TxCommand::ProposeXcm {
             transact,
             dry_run,
             fee_limit,
+            auto_fee,
+            fee_margin,
+            dest,
+            beneficiary_para,
+            weight,
+            weight_margin,
+            xcm_version,
+            threshold,
+            batch,
+            call,
         } => {
-            let fee_limit = (fee_limit * DOT_DECIMALS as f32) as u128;
-            println!("fee_limit set to: {}", fee_limit);
-
-            let withdraw_asset = WithdrawAsset(MultiAssets(vec![build_fee_asset(fee_limit)]));
+            let beneficiary_para = match beneficiary_para {
+                Some(para_id) => para_id,
+                None => {
+                    let para_id_query = eden::storage().parachain_info().parachain_id();
+                    api.storage()
+                        .at_latest()
+                        .await?
+                        .fetch_or_default(&para_id_query)
+                        .await?
+                        .0
+                }
+            };
+            println!("sending to {dest} with surplus deposited to para {beneficiary_para}");
 
-            let buy_execution = BuyExecution {
-                fees: build_fee_asset(fee_limit),
-                weight_limit: WeightLimit::Unlimited,
+            let relay = match &args.relay_url {
+                Some(relay_url) => Some(relay::connect(relay_url).await?),
+                None => None,
+            };
+            let relay_metadata = match (&relay, &args.relay_metadata) {
+                (Some(relay), _) => Some(relay.api.metadata()),
+                (None, Some(path)) => Some(relay::load_metadata(path)?),
+                (None, None) => None,
+            };
+            let relay_decimals = match (&relay, args.relay_decimals) {
+                (_, Some(decimals)) => decimals,
+                (Some(relay), None) => relay.token_decimals().await?,
+                (None, None) => {
+                    return Err("either --relay-url or --relay-decimals is required".into());
+                }
             };
+            let fee_limit = fee_limit.to_units(relay_decimals)?;
+            println!("fee_limit set to: {}", fee_limit);
 
-            let native_transact = hex::decode(transact)?;
-            let transact = Transact {
-                origin_kind: OriginKind::Native,
-                require_weight_at_most: Weight {
-                    ref_time: 10000000000,
-                    proof_size: 1000000,
-                },
-                call: DoubleEncoded {
-                    encoded: native_transact,
-                },
+            // Hex transactions are sent as they are without relay metadata, only templates and
+            // batches need it to be encoded.
+            let require_metadata = |what: &str| {
+                relay_metadata.as_ref().ok_or(format!(
+                    "{what} needs either --relay-url or --relay-metadata"
+                ))
             };
+            let mut native_transacts = Vec::new();
+            if let Some(call) = call {
+                native_transacts.push(call.encode(require_metadata("a template")?)?);
+            }
+            for transact in transact {
+                native_transacts.push(hex::decode(transact)?);
+            }
+            for native_transact in &native_transacts {
+                match &relay_metadata {
+                    Some(relay_metadata) => {
+                        let decoded = relay::decode_call(relay_metadata, native_transact)?;
+                        println!("transact: {decoded}");
+                    }
+                    None => println!(
+                        "transact: 0x{} (not decoded without --relay-url or --relay-metadata)",
+                        hex::encode(native_transact)
+                    ),
+                }
+            }
+            if batch == Batch::BatchAll && native_transacts.len() > 1 {
+                println!(
+                    "wrapping {} calls in Utility::batch_all",
+                    native_transacts.len()
+                );
+                let relay_metadata = require_metadata("batching several transactions")?;
+                native_transacts = vec![relay::batch_all(relay_metadata, &native_transacts)?];
+            }
 
-            let refund_surplus = RefundSurplus;
+            let mut transacts = Vec::with_capacity(native_transacts.len());
+            for native_transact in native_transacts {
+                let weight = match (weight, &relay) {
+                    (Some(weight), _) => weight,
+                    (None, Some(relay)) => {
+                        let estimated: TransactWeight =
+                            payment::query_call_info(&relay.api, &native_transact)
+                                .await?
+                                .weight
+                                .into();
+                        println!("estimated transact weight: {estimated}");
+                        estimated.with_margin(weight_margin)
+                    }
+                    (None, None) => {
+                        return Err("either --relay-url or --weight is required".into());
+                    }
+                };
+                println!("using transact weight: {weight}");
+                transacts.push(TransactCall {
+                    call: native_transact,
+                    weight,
+                });
+            }
 
-            let deposit_asset = DepositAsset {
-                assets: MultiAssetFilter::Wild(WildMultiAsset::All),
-                beneficiary: MultiLocation {
-                    parents: 0,
-                    interior: Junctions::X1(Junction::Parachain(2026)),
-                },
+            let xcm_version = match xcm_version {
+                Some(version) => version,
+                None => xcm::negotiate_version(&api, dest).await?,
             };
+            println!("using xcm version: {xcm_version}");
 
-            let dest = VersionedMultiLocation::V3(MultiLocation {
-                parents: 1,
-                interior: Junctions::Here,
-            });
+            let mut program = TransactProgram {
+                fee: fee_limit,
+                transacts,
+                beneficiary_para,
+            };
+            if auto_fee {
+                let relay = relay.as_ref().ok_or("--auto-fee requires --relay-url")?;
+                let (_, message) = program.clone().build(dest, xcm_version);
+                let estimated = relay
+                    .query_xcm_fee(&message, &dest.relay_token_id())
+                    .await?;
+                let fee = estimated.saturating_add(estimated.saturating_mul(fee_margin) / 100);
+                println!(
+                    "estimated xcm fee: {}, using {} with a {fee_margin}% margin",
+                    format_units(estimated, relay_decimals),
+                    format_units(fee, relay_decimals)
+                );
+                if fee > fee_limit {
+                    return Err(format!(
+                        "the estimated fee exceeds the fee limit of {}",
+                        format_units(fee_limit, relay_decimals)
+                    )
+                    .into());
+                }
+                program.fee = fee;
+            }
 
-            let message = VersionedXcm::V3(Xcm(vec![
-                withdraw_asset,
-                buy_execution,
-                transact,
-                refund_surplus,
-                deposit_asset,
-            ]));
+            let (dest, message) = program.build(dest, xcm_version);
 
             let send_xcm_call = RuntimeCall::PolkadotXcm(send {
                 message: Box::new(message),
                 dest: Box::new(dest),
             });
 
-            let technical_committee_call = RuntimeCall::Mandate(apply {
-                call: send_xcm_call.into(),
-            });
-
-            let members_query = eden::storage().technical_membership().members();
-            let members = api
-                .storage()
-                .at_latest()
-                .await?
-                .fetch(&members_query)
-                .await?
-                .unwrap();
-            let threshold = members.0.len() / 2 + 1;
-            println!("using tech committee threshold: {}", threshold);
-
-            let length_bound = technical_committee_call.encoded_size() as u32;
-
-            let technical_committee = eden::tx().technical_committee().propose(
-                threshold as u32,
-                technical_committee_call,
-                length_bound,
-            );
-
-            if dry_run {
-                let mocked = api.tx().call_data(&technical_committee)?;
-                let mocked = format!("0x{}", hex::encode(mocked));
-
-                println!("final extrinsic: {}", mocked);
-                println!(
-                    "shortlink: https://nodleprotocol.io/?rpc={}#/extrinsics/decode/{}",
-                    urlencoding::encode(&args.url),
-                    mocked
-                );
-            } else {
-                let events = api
-                    .tx()
-                    .sign_and_submit_then_watch_default(&technical_committee, &from)
-                    .await?
-                    .wait_for_finalized_success()
-                    .await?;
-
-                println!("events: {events:?}");
-            }
+            tc::propose(
+                &api,
+                &mut sender,
+                &args.url,
+                send_xcm_call,
+                threshold,
+                dry_run,
+            )
+            .await?;
         }
-        Commands::CreatePots { pots, starting_id } => {
+        TxCommand::ProposeLocal {
+            call,
+            dry_run,
+            threshold,
+        } => {
+            let call = tc::parse_local_call(&api.metadata(), &call)?;
+            tc::propose(&api, &mut sender, &args.url, call, threshold, dry_run).await?;
+        }
+        TxCommand::Tc { command } => tc::run(&api, &mut sender, command).await?,
+        TxCommand::Multisig { command } => {
+            let multisig = args.multisig.as_ref().ok_or("--multisig is required")?;
+            multisig::run(&api, &mut sender, multisig, command).await?
+        }
+        TxCommand::CreatePots {
+            pots,
+            starting_id,
+            sponsorship_type,
+            fee_quota,
+            reserve_quota,
+            update_existing,
+        } => {
             println!("Creating {pots} pots... ");
-            let mut tx_progresses = VecDeque::new();
-            for i in starting_id..pots.saturating_add(starting_id) {
-                println!("Creating pot {}/{}", i, pots);
-                let create_pot = eden::tx().sponsorship().create_pot(
-                    i,
-                    SponsorshipType::AnySafe,
-                    123 * NODL_DECIMALS,
-                    9 * NODL_DECIMALS,
-                );
-
-                let params = nodle::NodleExtrinsicParamsBuilder::default()
-                    .nonce(nonce)
-                    .build();
-                let tx = api.tx().create_signed(&create_pot, &from, params).await?;
-                let tx_progress = tx.submit_and_watch().await?;
-                tx_progresses.push_back(tx_progress);
-                nonce += 1;
-            }
-            while let Some(tx_progress) = tx_progresses.pop_front() {
-                tx_progress.wait_for_finalized_success().await?;
-            }
+            let limits = PotLimits {
+                sponsorship_type: sponsorship_type.into(),
+                fee_quota: fee_quota.to_units(nodle::TOKEN_DECIMALS)?,
+                reserve_quota: reserve_quota.to_units(nodle::TOKEN_DECIMALS)?,
+            };
+            sponsorship::create_pots(
+                &api,
+                &mut sender,
+                journal.as_mut(),
+                starting_id..pots.saturating_add(starting_id),
+                &limits,
+                update_existing,
+            )
+            .await?;
             println!("Done!");
         }
-        Commands::RegisterUsers {
+        TxCommand::RegisterUsers {
             pot_id,
             users,
-            starting_id,
+            export,
+            fee_quota,
+            reserve_quota,
         } => {
-            println!("Registering {users} users... ");
-            let chunked = (starting_id..users.saturating_add(starting_id))
-                .into_iter()
-                .collect::<Vec<_>>()
-                .chunks(MAX_USERS_ONE_BLOCK)
-                .map(|chunk| {
-                    chunk
-                        .into_iter()
-                        .filter_map(|&i| {
-                            SecretUri::from_str(format!("//Alice/{i}").as_str())
-                                .map(|s| {
-                                    sr25519::Keypair::from_uri(&s).map(|k| k.public_key().into())
-                                })
-                                .map_or(None, |r| r.ok())
-                        })
-                        .collect::<Vec<_>>()
-                })
-                .collect::<Vec<_>>();
-            let mut tx_progresses = VecDeque::new();
-            for chunk in chunked {
-                println!(
-                    "Registering {chunk_len} users / {users}",
-                    chunk_len = chunk.len()
-                );
-                let register_user = eden::tx().sponsorship().register_users(
-                    pot_id,
-                    chunk,
-                    43 * NODL_DECIMALS,
-                    7 * NODL_DECIMALS,
-                );
-
-                let params = nodle::NodleExtrinsicParamsBuilder::default()
-                    .nonce(nonce)
-                    .build();
-                let tx = api
-                    .tx()
-                    .create_signed(&register_user, &from, params)
-                    .await?;
-                let tx_progress = tx.submit_and_watch().await?;
-
-                tx_progresses.push_back(tx_progress);
-                nonce += 1;
-            }
-            while let Some(tx_progress) = tx_progresses.pop_front() {
-                tx_progress.wait_for_finalized_success().await?;
-            }
+            let limits = DefaultLimits {
+                fee_quota: fee_quota.to_units(nodle::TOKEN_DECIMALS)?,
+                reserve_quota: reserve_quota.to_units(nodle::TOKEN_DECIMALS)?,
+            };
+            let ss58_prefix = api
+                .constants()
+                .at(&eden::constants().system().ss58_prefix())?;
+            let loaded = users.load(pot_id, ss58_prefix)?;
+            export.export(&users, &loaded, pot_id, ss58_prefix)?;
+            let new_users = users::with_limits(loaded, &limits)?;
+            println!("Registering {} users... ", new_users.len());
+            sponsorship::register_users(&api, &mut sender, journal.as_mut(), pot_id, &new_users)
+                .await?;
+            println!("Done!");
+        }
+        TxCommand::RemoveUsers { pot_id, users } => {
+            let ss58_prefix = api
+                .constants()
+                .at(&eden::constants().system().ss58_prefix())?;
+            let old_users = users.load(pot_id, ss58_prefix)?;
+            println!("Removing {} users... ", old_users.len());
+            sponsorship::remove_users(&api, &mut sender, pot_id, &old_users).await?;
+            println!("Done!");
+        }
+        TxCommand::RemovePots { pots, starting_id } => {
+            println!("Removing {pots} pots... ");
+            sponsorship::remove_pots(
+                &api,
+                &mut sender,
+                starting_id..pots.saturating_add(starting_id),
+            )
+            .await?;
             println!("Done!");
         }
     };
 
-    Ok(())
+    sender.finish()
 }
@@ -0,0 +1,351 @@
+//! Sending calls from a multisig account through `pallet_multisig`
+
+use std::fmt;
+use std::str::FromStr;
+
+use clap::Subcommand;
+use codec::{DecodeAll, Encode};
+use subxt::blocks::ExtrinsicEvents;
+use subxt::config::{Config, Hasher};
+use subxt::utils::{AccountId32, H256};
+use subxt::OnlineClient;
+
+use crate::eden::{
+    self,
+    multisig::events,
+    runtime_types::{
+        pallet_multisig::{Multisig, Timepoint},
+        runtime_eden::RuntimeCall,
+        sp_weights::weight_v2::Weight,
+    },
+};
+use crate::nodle::NodleConfig;
+use crate::payment;
+use crate::tx::Sender;
+
+/// A pending multisig operation as stored in `Multisig::Multisigs`.
+type PendingCall = Multisig<u32, u128, AccountId32>;
+
+#[derive(Debug, Subcommand)]
+pub enum MultisigCommand {
+    /// Lists the calls of the multisig account waiting for approvals
+    List,
+    /// Approves a call of the multisig account, executing it if this is the last approval needed
+    Approve {
+        /// The hash of the call to approve.
+        #[arg(long)]
+        hash: H256,
+        /// The call itself, scale encoded in hex. It is required for the last approval, which
+        /// executes the call, and optional otherwise.
+        #[arg(long)]
+        call: Option<String>,
+    },
+    /// Cancels a call of the multisig account, which only its first approver can do
+    Cancel {
+        /// The hash of the call to cancel.
+        #[arg(long)]
+        hash: H256,
+    },
+}
+
+/// A multisig account given as its signatories and the number of approvals its calls need.
+#[derive(Debug, Clone)]
+pub struct MultisigAccount {
+    /// The signatories sorted and deduplicated, as `pallet_multisig` expects them.
+    signatories: Vec<AccountId32>,
+    threshold: u16,
+}
+
+impl MultisigAccount {
+    /// The account id derived from the signatories and the threshold, the same way
+    /// `pallet_multisig::multi_account_id` does.
+    pub fn account_id(&self) -> AccountId32 {
+        let entropy = (b"modlpy/utilisuba", &self.signatories, self.threshold).encode();
+        AccountId32(<NodleConfig as Config>::Hasher::hash(&entropy).0)
+    }
+
+    /// The number of signatories that must approve a call before it is executed.
+    pub fn threshold(&self) -> u16 {
+        self.threshold
+    }
+
+    /// The signatories other than `signer`, failing if `signer` is not one of them.
+    pub fn other_signatories(&self, signer: &AccountId32) -> Result<Vec<AccountId32>, String> {
+        if !self.signatories.contains(signer) {
+            return Err(format!("{signer} is not a signatory of the multisig"));
+        }
+        Ok(self
+            .signatories
+            .iter()
+            .filter(|&signatory| signatory != signer)
+            .cloned()
+            .collect())
+    }
+
+    /// Reads the pending operation for the call with `call_hash`, if any.
+    async fn pending(
+        &self,
+        api: &OnlineClient<NodleConfig>,
+        call_hash: [u8; 32],
+    ) -> Result<Option<PendingCall>, subxt::Error> {
+        let multisig_query = eden::storage()
+            .multisig()
+            .multisigs(self.account_id(), call_hash);
+        api.storage()
+            .at_latest()
+            .await?
+            .fetch(&multisig_query)
+            .await
+    }
+
+    /// Wraps the scale encoded `call` so that `signer` approves it on behalf of the multisig,
+    /// through `Multisig::as_multi` with the timepoint of the first approval if there is one.
+    pub async fn wrap(
+        &self,
+        api: &OnlineClient<NodleConfig>,
+        signer: &AccountId32,
+        call: Vec<u8>,
+    ) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let other_signatories = self.other_signatories(signer)?;
+        let runtime_call = RuntimeCall::decode_all(&mut &call[..])?;
+        if self.threshold == 1 {
+            let as_multi = eden::tx()
+                .multisig()
+                .as_multi_threshold_1(other_signatories, runtime_call);
+            return Ok(api.tx().call_data(&as_multi)?);
+        }
+
+        let call_hash = <NodleConfig as Config>::Hasher::hash(&call).0;
+        let maybe_timepoint = self.pending(api, call_hash).await?.map(|pending| {
+            println!(
+                "approving multisig call {:?} with {} of {} approvals",
+                H256(call_hash),
+                pending.approvals.0.len(),
+                self.threshold
+            );
+            pending.when
+        });
+        if maybe_timepoint.is_none() {
+            println!("opening multisig call {:?}", H256(call_hash));
+        }
+
+        let max_weight = payment::query_call_info(api, &call).await?.weight;
+        let as_multi = eden::tx().multisig().as_multi(
+            self.threshold,
+            other_signatories,
+            maybe_timepoint,
+            runtime_call,
+            max_weight,
+        );
+        Ok(api.tx().call_data(&as_multi)?)
+    }
+}
+
+impl FromStr for MultisigAccount {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (signatories, threshold) = s
+            .split_once(';')
+            .ok_or_else(|| format!("invalid multisig `{s}`, expected signatories,...;threshold"))?;
+        let mut signatories = signatories
+            .split(',')
+            .map(|signatory| {
+                AccountId32::from_str(signatory.trim())
+                    .map_err(|e| format!("invalid signatory `{signatory}`: {e}"))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        signatories.sort();
+        signatories.dedup();
+        if signatories.len() < 2 {
+            return Err("a multisig needs at least 2 distinct signatories".to_string());
+        }
+
+        let threshold: u16 = threshold
+            .trim()
+            .parse()
+            .map_err(|_| format!("invalid multisig threshold `{threshold}`"))?;
+        if threshold == 0 || threshold as usize > signatories.len() {
+            return Err(format!(
+                "threshold {threshold} is invalid for a multisig of {} signatories",
+                signatories.len()
+            ));
+        }
+
+        Ok(MultisigAccount {
+            signatories,
+            threshold,
+        })
+    }
+}
+
+impl fmt::Display for MultisigAccount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({} of {} signatories)",
+            self.account_id(),
+            self.threshold,
+            self.signatories.len()
+        )
+    }
+}
+
+fn print_timepoint(timepoint: &Timepoint<u32>) -> String {
+    format!("block {} index {}", timepoint.height, timepoint.index)
+}
+
+/// Prints every call of the multisig account waiting for approvals.
+async fn list_pending(
+    api: &OnlineClient<NodleConfig>,
+    multisig: &MultisigAccount,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let multisigs_query = eden::storage()
+        .multisig()
+        .multisigs_iter1(multisig.account_id());
+    let mut pending_calls = api
+        .storage()
+        .at_latest()
+        .await?
+        .iter(multisigs_query)
+        .await?;
+
+    let mut count = 0;
+    while let Some(pending_call) = pending_calls.next().await {
+        let pending_call = pending_call?;
+        // The call hash is the last key, stored as is after its blake2_128 hash.
+        let call_hash = &pending_call.key_bytes[pending_call.key_bytes.len() - 32..];
+        let pending = pending_call.value;
+        println!();
+        println!("hash: 0x{}", hex::encode(call_hash));
+        println!("opened at: {}", print_timepoint(&pending.when));
+        println!("depositor: {} ({})", pending.depositor, pending.deposit);
+        println!(
+            "approvals ({} of {}): {:?}",
+            pending.approvals.0.len(),
+            multisig.threshold,
+            pending
+                .approvals
+                .0
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+        );
+        count += 1;
+    }
+    println!("{count} pending call(s)");
+
+    Ok(())
+}
+
+/// Prints the multisig events emitted by an approval or a cancellation.
+fn report_events(events: &ExtrinsicEvents<NodleConfig>) -> Result<(), subxt::Error> {
+    for new_multisig in events.find::<events::NewMultisig>() {
+        println!("opened: {:?}", H256(new_multisig?.call_hash));
+    }
+    for approval in events.find::<events::MultisigApproval>() {
+        let approval = approval?;
+        println!(
+            "approved: {:?} opened at {}",
+            H256(approval.call_hash),
+            print_timepoint(&approval.timepoint)
+        );
+    }
+    for executed in events.find::<events::MultisigExecuted>() {
+        let executed = executed?;
+        println!(
+            "executed: {:?} with result {:?}",
+            H256(executed.call_hash),
+            executed.result
+        );
+    }
+    for cancelled in events.find::<events::MultisigCancelled>() {
+        println!("cancelled: {:?}", H256(cancelled?.call_hash));
+    }
+    Ok(())
+}
+
+pub async fn run(
+    api: &OnlineClient<NodleConfig>,
+    sender: &mut Sender,
+    multisig: &MultisigAccount,
+    command: MultisigCommand,
+) -> Result<(), Box<dyn std::error::Error>> {
+    println!("multisig: {multisig}");
+    let other_signatories = multisig.other_signatories(sender.account())?;
+
+    let events = match command {
+        MultisigCommand::List => return list_pending(api, multisig).await,
+        MultisigCommand::Approve { hash, call } => {
+            let pending = multisig
+                .pending(api, hash.0)
+                .await?
+                .ok_or_else(|| format!("no pending multisig call with hash {hash:?}"))?;
+            if pending.approvals.0.contains(sender.account()) {
+                return Err("the signer already approved this call".into());
+            }
+            let last_approval = pending.approvals.0.len() + 1 >= multisig.threshold as usize;
+
+            match call {
+                Some(call) => {
+                    let call = hex::decode(call.trim_start_matches("0x"))?;
+                    if <NodleConfig as Config>::Hasher::hash(&call) != hash {
+                        return Err(format!("the call does not match the hash {hash:?}").into());
+                    }
+                    let max_weight = payment::query_call_info(api, &call).await?.weight;
+                    let as_multi = eden::tx().multisig().as_multi(
+                        multisig.threshold,
+                        other_signatories,
+                        Some(pending.when),
+                        RuntimeCall::decode_all(&mut &call[..])?,
+                        max_weight,
+                    );
+                    sender.send(&as_multi).await?
+                }
+                None => {
+                    if last_approval {
+                        println!(
+                            "this is the last approval needed, pass the call with --call to execute it"
+                        );
+                    }
+                    let approve_as_multi = eden::tx().multisig().approve_as_multi(
+                        multisig.threshold,
+                        other_signatories,
+                        Some(pending.when),
+                        hash.0,
+                        Weight {
+                            ref_time: 0,
+                            proof_size: 0,
+                        },
+                    );
+                    sender.send(&approve_as_multi).await?
+                }
+            }
+        }
+        MultisigCommand::Cancel { hash } => {
+            let pending = multisig
+                .pending(api, hash.0)
+                .await?
+                .ok_or_else(|| format!("no pending multisig call with hash {hash:?}"))?;
+            if &pending.depositor != sender.account() {
+                return Err(format!(
+                    "only the first approver {} can cancel this call",
+                    pending.depositor
+                )
+                .into());
+            }
+            let cancel_as_multi = eden::tx().multisig().cancel_as_multi(
+                multisig.threshold,
+                other_signatories,
+                pending.when,
+                hash.0,
+            );
+            sender.send(&cancel_as_multi).await?
+        }
+    };
+
+    if let Some(events) = events {
+        report_events(&events)?;
+    }
+    Ok(())
+}
@@ -50,6 +50,9 @@ impl<T: Config> ExtrinsicParams<T> for ChargeSponsor {
 }
 impl ExtrinsicParamsEncoder for ChargeSponsor {}
 
+/// The number of decimals of NODL, the native token that fees and tips are paid in.
+pub const TOKEN_DECIMALS: u32 = 11;
+
 /// A struct representing the signed extra and additional parameters required
 /// to construct a transaction for a Nodle node.
 pub type NodleExtrinsicParams<T> = signed_extensions::AnyOf<
@@ -112,7 +115,6 @@ impl<T: Config> NodleExtrinsicParamsBuilder<T> {
     /// Make the transaction mortal, given a block header that it should be mortal from,
     /// and the number of blocks (roughly; it'll be rounded to a power of two) that it will
     /// be mortal for.
-    #[allow(dead_code)]
     pub fn mortal(mut self, from_block: &T::Header, for_n_blocks: u64) -> Self {
         self.mortality = Some(Mortality {
             checkpoint_hash: from_block.hash(),
@@ -123,7 +125,6 @@ impl<T: Config> NodleExtrinsicParamsBuilder<T> {
     }
 
     /// Provide a tip to the block author in the chain's native token.
-    #[allow(dead_code)]
     pub fn tip(mut self, tip: u128) -> Self {
         self.tip = tip;
         self
@@ -0,0 +1,326 @@
+//! Transaction files exchanged with an offline machine holding the signing key
+//!
+//! `busypot build` writes an [`UnsignedPayload`] while connected to the chain, `busypot sign` turns
+//! it into a [`SignedPayload`] without any network access and `busypot submit` sends the signed
+//! transactions to the chain.
+
+use std::fmt;
+use std::fs::File;
+use std::path::Path;
+
+use codec::{Compact, DecodeAll, Encode};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use subxt::config::{Config, ExtrinsicParams, ExtrinsicParamsEncoder, Hasher};
+use subxt::tx::{Signer, TxPayload};
+use subxt::utils::{AccountId32, Era, MultiSignature, H256};
+use subxt::OnlineClient;
+
+use crate::amount::format_units;
+use crate::eden::runtime_types::runtime_eden::RuntimeCall;
+use crate::nodle::{NodleConfig, NodleExtrinsicParams, TOKEN_DECIMALS};
+use crate::signer::Keypair;
+use crate::tc;
+use crate::tx::{self, Extrinsic, Params};
+
+/// Transactions composed online, waiting to be signed by `account`.
+#[derive(Serialize, Deserialize)]
+pub struct UnsignedPayload {
+    pub genesis_hash: H256,
+    pub spec_version: u32,
+    pub transaction_version: u32,
+    pub account: AccountId32,
+    pub transactions: Vec<UnsignedTransaction>,
+}
+
+/// A transaction split into the parts making up the payload to sign, with the signed extensions
+/// already encoded the way `NodleExtrinsicParams` does.
+#[derive(Serialize, Deserialize)]
+pub struct UnsignedTransaction {
+    pub nonce: u64,
+    #[serde(with = "hex_bytes")]
+    pub call_data: Vec<u8>,
+    /// The signed extensions data included in the transaction.
+    #[serde(with = "hex_bytes")]
+    pub extra: Vec<u8>,
+    /// The signed extensions data only included in the signed payload, such as the genesis hash.
+    #[serde(with = "hex_bytes")]
+    pub additional: Vec<u8>,
+}
+
+/// Transactions signed offline, ready to be submitted.
+#[derive(Serialize, Deserialize)]
+pub struct SignedPayload {
+    pub genesis_hash: H256,
+    pub spec_version: u32,
+    pub transactions: Vec<SignedTransaction>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SignedTransaction {
+    pub nonce: u64,
+    #[serde(with = "hex_bytes")]
+    pub extrinsic: Vec<u8>,
+}
+
+/// The signed extensions of a transaction, as encoded by `NodleExtrinsicParams` in the order of
+/// the runtime: `CheckMortality`, `CheckNonce` and `ChargeTransactionPayment` in the extra data,
+/// then `CheckSpecVersion`, `CheckTxVersion`, `CheckGenesis` and `CheckMortality` in the
+/// additional data.
+struct SignedExtensions {
+    era: Era,
+    nonce: u64,
+    tip: u128,
+    spec_version: u32,
+    transaction_version: u32,
+    genesis_hash: H256,
+    /// The block the mortality period starts from, or the genesis block for immortal
+    /// transactions.
+    checkpoint: H256,
+}
+
+impl SignedExtensions {
+    /// Fails unless the transaction is signed for the chain, runtime and nonce of `payload` and
+    /// `transaction`, so that the header printed to the signer describes what is signed.
+    fn check(
+        &self,
+        payload: &UnsignedPayload,
+        transaction: &UnsignedTransaction,
+    ) -> Result<(), String> {
+        let nonce = transaction.nonce;
+        if self.genesis_hash != payload.genesis_hash {
+            return Err(format!(
+                "the transaction with nonce {nonce} is for the chain with genesis hash {:?}",
+                self.genesis_hash
+            ));
+        }
+        if self.spec_version != payload.spec_version
+            || self.transaction_version != payload.transaction_version
+        {
+            return Err(format!(
+                "the transaction with nonce {nonce} is for spec version {} and transaction \
+                 version {}",
+                self.spec_version, self.transaction_version
+            ));
+        }
+        if self.nonce != nonce {
+            return Err(format!(
+                "the transaction listed with nonce {nonce} is signed with nonce {}",
+                self.nonce
+            ));
+        }
+        if self.era == Era::Immortal && self.checkpoint != self.genesis_hash {
+            return Err(format!(
+                "the immortal transaction with nonce {nonce} does not start from the genesis block"
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for SignedExtensions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "tip: {} NODL, ", format_units(self.tip, TOKEN_DECIMALS))?;
+        match self.era {
+            Era::Immortal => write!(f, "immortal"),
+            Era::Mortal { period, .. } => write!(
+                f,
+                "valid for {period} blocks from block {:?}",
+                self.checkpoint
+            ),
+        }
+    }
+}
+
+impl UnsignedTransaction {
+    /// Composes `call` for our parachain with `params` setting `nonce`, leaving it to be signed
+    /// later.
+    pub fn new<Call: TxPayload>(
+        api: &OnlineClient<NodleConfig>,
+        call: &Call,
+        nonce: u64,
+        params: Params,
+    ) -> Result<Self, subxt::Error> {
+        let call_data = api.tx().call_data(call)?;
+        let params = <NodleExtrinsicParams<NodleConfig> as ExtrinsicParams<NodleConfig>>::new(
+            api.clone(),
+            params,
+        )?;
+
+        let mut extra = Vec::new();
+        params.encode_extra_to(&mut extra);
+        let mut additional = Vec::new();
+        params.encode_additional_to(&mut additional);
+
+        Ok(UnsignedTransaction {
+            nonce,
+            call_data,
+            extra,
+            additional,
+        })
+    }
+
+    /// Decodes the signed extensions of the transaction from `extra` and `additional`.
+    fn signed_extensions(&self) -> Result<SignedExtensions, codec::Error> {
+        let (era, Compact(nonce), Compact(tip)) =
+            <(Era, Compact<u64>, Compact<u128>)>::decode_all(&mut &self.extra[..])?;
+        let (spec_version, transaction_version, genesis_hash, checkpoint) =
+            <(u32, u32, H256, H256)>::decode_all(&mut &self.additional[..])?;
+        Ok(SignedExtensions {
+            era,
+            nonce,
+            tip,
+            spec_version,
+            transaction_version,
+            genesis_hash,
+            checkpoint,
+        })
+    }
+
+    /// The bytes to sign, hashed when longer than 256 bytes as the runtime expects.
+    fn signer_payload(&self) -> Vec<u8> {
+        let payload = [&self.call_data[..], &self.extra, &self.additional].concat();
+        if payload.len() > 256 {
+            <NodleConfig as Config>::Hasher::hash(&payload).0.to_vec()
+        } else {
+            payload
+        }
+    }
+
+    /// Encodes the transaction with an empty signature from `account`, which is enough for
+    /// estimating its fee before it is signed.
+    pub fn with_empty_signature(&self, account: &AccountId32) -> Vec<u8> {
+        self.signed(account, MultiSignature::Sr25519([0; 64]))
+    }
+
+    /// Encodes the transaction signed by `account`, the same way subxt does when signing online.
+    fn signed(&self, account: &AccountId32, signature: MultiSignature) -> Vec<u8> {
+        let address: <NodleConfig as Config>::Address = account.clone().into();
+
+        // "is signed" + transaction protocol version (4)
+        let mut encoded_inner = vec![0b1000_0000 + 4];
+        address.encode_to(&mut encoded_inner);
+        signature.encode_to(&mut encoded_inner);
+        encoded_inner.extend(&self.extra);
+        encoded_inner.extend(&self.call_data);
+
+        let mut encoded = Compact(encoded_inner.len() as u32).encode();
+        encoded.extend(encoded_inner);
+        encoded
+    }
+}
+
+/// Signs the transactions of the unsigned payload at `input` with `signer`, writing them to `out`.
+///
+/// Every call is decoded and printed along with its tip and mortality before being signed so that
+/// it can be reviewed on the offline machine. The signed extensions of every transaction must
+/// match the chain, runtime and nonce given in the payload, or nothing is signed.
+pub fn sign(input: &Path, out: &Path, signer: &Keypair) -> Result<(), Box<dyn std::error::Error>> {
+    let payload: UnsignedPayload = read_json(input)?;
+    let account = signer.account_id();
+    if account != payload.account {
+        return Err(format!(
+            "the transactions are meant to be signed by {}, not {account}",
+            payload.account
+        )
+        .into());
+    }
+    println!("genesis hash: {:?}", payload.genesis_hash);
+    println!(
+        "spec version: {}, transaction version: {}",
+        payload.spec_version, payload.transaction_version
+    );
+
+    let mut transactions = Vec::with_capacity(payload.transactions.len());
+    for transaction in &payload.transactions {
+        let extensions = transaction.signed_extensions().map_err(|e| {
+            format!(
+                "failed to decode the signed extensions of the transaction with nonce {}: {e}",
+                transaction.nonce
+            )
+        })?;
+        extensions.check(&payload, transaction)?;
+        println!("nonce {} ({extensions}):", transaction.nonce);
+        match RuntimeCall::decode_all(&mut &transaction.call_data[..]) {
+            Ok(call) => tc::print_call(&call, "    "),
+            Err(e) => println!(
+                "    0x{} (failed to decode: {e})",
+                hex::encode(&transaction.call_data)
+            ),
+        }
+
+        let signature = signer.sign(&transaction.signer_payload());
+        transactions.push(SignedTransaction {
+            nonce: transaction.nonce,
+            extrinsic: transaction.signed(&account, signature),
+        });
+    }
+
+    let signed = SignedPayload {
+        genesis_hash: payload.genesis_hash,
+        spec_version: payload.spec_version,
+        transactions,
+    };
+    write_json(out, &signed)?;
+    println!(
+        "wrote {} signed transaction(s) to {}",
+        signed.transactions.len(),
+        out.display()
+    );
+    Ok(())
+}
+
+/// Submits the transactions of the signed payload at `input`, then waits for all of them to be
+/// finalized.
+pub async fn submit(
+    api: &OnlineClient<NodleConfig>,
+    input: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let signed: SignedPayload = read_json(input)?;
+    if signed.genesis_hash != api.genesis_hash() {
+        return Err("the transactions were signed for another chain".into());
+    }
+    let spec_version = api.runtime_version().spec_version;
+    if signed.spec_version != spec_version {
+        return Err(format!(
+            "the transactions were signed for spec version {}, but the chain is now at {spec_version}",
+            signed.spec_version
+        )
+        .into());
+    }
+
+    println!("submitting {} transaction(s)", signed.transactions.len());
+    let extrinsics = signed
+        .transactions
+        .into_iter()
+        .map(|transaction| Extrinsic::from_bytes(api.clone(), transaction.extrinsic))
+        .collect();
+    for events in tx::submit_all(extrinsics).await? {
+        println!("finalized: {:?}", events.extrinsic_hash());
+    }
+    Ok(())
+}
+
+/// Writes `value` to `path` as pretty printed JSON.
+pub fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<(), Box<dyn std::error::Error>> {
+    serde_json::to_writer_pretty(File::create(path)?, value)?;
+    Ok(())
+}
+
+fn read_json<T: DeserializeOwned>(path: &Path) -> Result<T, Box<dyn std::error::Error>> {
+    Ok(serde_json::from_reader(File::open(path)?)?)
+}
+
+/// (De)serializes bytes as 0x prefixed hex strings.
+mod hex_bytes {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("0x{}", hex::encode(bytes)))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        hex::decode(s.trim_start_matches("0x")).map_err(serde::de::Error::custom)
+    }
+}
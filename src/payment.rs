@@ -0,0 +1,47 @@
+//! Queries against the transaction payment runtime apis of a chain
+
+use codec::{Decode, Encode};
+use subxt::{Config, OnlineClient};
+
+use crate::eden::runtime_types::sp_weights::weight_v2::Weight;
+
+/// The subset of `pallet_transaction_payment::RuntimeDispatchInfo` that we care about.
+#[derive(Decode)]
+pub struct RuntimeDispatchInfo {
+    pub weight: Weight,
+    _class: u8,
+    pub partial_fee: u128,
+}
+
+/// Asks the runtime of the chain behind `api` for the dispatch info of the encoded `call`.
+pub async fn query_call_info<T: Config>(
+    api: &OnlineClient<T>,
+    call: &[u8],
+) -> Result<RuntimeDispatchInfo, subxt::Error> {
+    // The runtime api expects the call itself followed by its encoded length. Since the call is
+    // already scale encoded, we append the length rather than encoding the bytes as a `Vec`.
+    let mut params = call.to_vec();
+    (call.len() as u32).encode_to(&mut params);
+
+    api.runtime_api()
+        .at_latest()
+        .await?
+        .call_raw("TransactionPaymentCallApi_query_call_info", Some(&params))
+        .await
+}
+
+/// Asks the runtime of the chain behind `api` for the dispatch info of the encoded `extrinsic`,
+/// including the fee it would pay without its tip.
+pub async fn query_info<T: Config>(
+    api: &OnlineClient<T>,
+    extrinsic: &[u8],
+) -> Result<RuntimeDispatchInfo, subxt::Error> {
+    let mut params = extrinsic.to_vec();
+    (extrinsic.len() as u32).encode_to(&mut params);
+
+    api.runtime_api()
+        .at_latest()
+        .await?
+        .call_raw("TransactionPaymentApi_query_info", Some(&params))
+        .await
+}
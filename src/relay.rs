@@ -0,0 +1,263 @@
+//! Queries against the chain receiving the xcm sent by `propose-xcm`
+
+use std::fmt;
+use std::path::Path;
+
+use clap::Subcommand;
+use codec::{Compact, Decode, Encode};
+use subxt::backend::{legacy::LegacyRpcMethods, rpc::RpcClient};
+use subxt::ext::scale_value::{self, Value};
+use subxt::tx::TxPayload;
+use subxt::{Metadata, OnlineClient, PolkadotConfig};
+
+use crate::eden::runtime_types::{
+    sp_weights::weight_v2::Weight,
+    xcm::{VersionedAssetId, VersionedXcm},
+};
+
+/// A client connected to the relay chain, or to the system parachain the xcm is sent to.
+pub type RelayClient = OnlineClient<PolkadotConfig>;
+
+/// The errors returned by the `XcmPaymentApi` runtime api.
+#[derive(Debug, Decode)]
+enum XcmPaymentApiError {
+    Unimplemented,
+    VersionedConversionFailed,
+    WeightNotComputable,
+    UnhandledXcmVersion,
+    AssetNotFound,
+    Unroutable,
+}
+
+/// The connection to the relay chain, or to the system parachain the xcm is sent to.
+pub struct Relay {
+    pub api: RelayClient,
+    rpc: LegacyRpcMethods<PolkadotConfig>,
+}
+
+impl Relay {
+    /// Reads the number of decimals of the chain's native token from its system properties.
+    pub async fn token_decimals(&self) -> Result<u32, Box<dyn std::error::Error>> {
+        let properties = self.rpc.system_properties().await?;
+        let decimals = match properties.get("tokenDecimals") {
+            // Chains with several tokens list the decimals of the native token first.
+            Some(serde_json::Value::Array(decimals)) => decimals.first().and_then(|d| d.as_u64()),
+            Some(decimals) => decimals.as_u64(),
+            None => None,
+        };
+        decimals
+            .map(|d| d as u32)
+            .ok_or_else(|| "the relay chain does not advertise its token decimals".into())
+    }
+
+    /// Asks the chain for the fee of executing `message`, when paid in `asset`.
+    pub async fn query_xcm_fee(
+        &self,
+        message: &VersionedXcm,
+        asset: &VersionedAssetId,
+    ) -> Result<u128, Box<dyn std::error::Error>> {
+        let runtime_api = self.api.runtime_api().at_latest().await?;
+
+        let weight: Result<Weight, XcmPaymentApiError> = runtime_api
+            .call_raw("XcmPaymentApi_query_xcm_weight", Some(&message.encode()))
+            .await?;
+        let weight = weight.map_err(|e| format!("failed to compute the xcm weight: {e:?}"))?;
+
+        let params = (weight, asset).encode();
+        let fee: Result<u128, XcmPaymentApiError> = runtime_api
+            .call_raw("XcmPaymentApi_query_weight_to_asset_fee", Some(&params))
+            .await?;
+        Ok(fee.map_err(|e| format!("failed to compute the xcm fee: {e:?}"))?)
+    }
+}
+
+/// Connects to the relay chain RPC endpoint at `url`.
+pub async fn connect(url: &str) -> Result<Relay, subxt::Error> {
+    let rpc_client = RpcClient::from_url(url).await?;
+    Ok(Relay {
+        api: RelayClient::from_rpc_client(rpc_client.clone()).await?,
+        rpc: LegacyRpcMethods::new(rpc_client),
+    })
+}
+
+/// Loads the relay chain metadata from a `.scale` file, such as the ones produced by
+/// `subxt metadata`.
+pub fn load_metadata(path: &Path) -> Result<Metadata, Box<dyn std::error::Error>> {
+    let bytes = std::fs::read(path)?;
+    Ok(Metadata::decode(&mut &bytes[..])?)
+}
+
+/// A relay chain call decoded against the relay chain metadata.
+pub struct DecodedCall {
+    pub pallet: String,
+    pub call: String,
+    pub args: Vec<(String, Value<u32>)>,
+}
+
+impl fmt::Display for DecodedCall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}::{}", self.pallet, self.call)?;
+        for (name, value) in &self.args {
+            write!(f, "\n    {name}: {value}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Decodes the scale encoded `call` into its pallet, call and named arguments.
+///
+/// Fails unless `call` is exactly one valid call of the chain described by `metadata`.
+pub fn decode_call(
+    metadata: &Metadata,
+    call: &[u8],
+) -> Result<DecodedCall, Box<dyn std::error::Error>> {
+    let (&pallet_index, rest) = call.split_first().ok_or("the call is empty")?;
+    let (&call_index, mut args) = rest.split_first().ok_or("the call index is missing")?;
+
+    let pallet = metadata.pallet_by_index_err(pallet_index)?;
+    let variant = pallet
+        .call_variant_by_index(call_index)
+        .ok_or_else(|| format!("call {call_index} not found in pallet {}", pallet.name()))?;
+
+    let mut decoded = DecodedCall {
+        pallet: pallet.name().to_string(),
+        call: variant.name.clone(),
+        args: Vec::with_capacity(variant.fields.len()),
+    };
+    for (i, field) in variant.fields.iter().enumerate() {
+        let value = scale_value::scale::decode_as_type(&mut args, &field.ty.id, metadata.types());
+        let value = value
+            .map_err(|e| format!("failed to decode {}::{}: {e}", decoded.pallet, decoded.call))?;
+        let name = field.name.clone().unwrap_or_else(|| i.to_string());
+        decoded.args.push((name, value));
+    }
+
+    if !args.is_empty() {
+        return Err(format!(
+            "{} trailing bytes after decoding {}::{}",
+            args.len(),
+            decoded.pallet,
+            decoded.call
+        )
+        .into());
+    }
+
+    Ok(decoded)
+}
+
+/// Wraps the scale encoded `calls` in a single `Utility::batch_all` call.
+pub fn batch_all(
+    metadata: &Metadata,
+    calls: &[Vec<u8>],
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let pallet = metadata.pallet_by_name_err("Utility")?;
+    let variant = pallet
+        .call_variant_by_name("batch_all")
+        .ok_or("call batch_all not found in pallet Utility")?;
+
+    // The calls are already encoded, so we encode the `Vec<RuntimeCall>` argument by hand.
+    let mut encoded = vec![pallet.index(), variant.index];
+    Compact(calls.len() as u32).encode_to(&mut encoded);
+    for call in calls {
+        encoded.extend_from_slice(call);
+    }
+    Ok(encoded)
+}
+
+/// Relay chain calls commonly proposed by the technical committee.
+#[derive(Debug, Subcommand)]
+pub enum RelayCall {
+    /// Adds the registrar lock to a parachain, through `Registrar::add_lock`
+    Lock {
+        /// The para id to lock.
+        #[arg(long)]
+        para: u32,
+    },
+    /// Removes the registrar lock of a parachain, through `Registrar::remove_lock`
+    Unlock {
+        /// The para id to unlock.
+        #[arg(long)]
+        para: u32,
+    },
+    /// Registers the intent of swapping the slots of two parachains, through `Registrar::swap`
+    Swap {
+        /// The para id initiating the swap, usually ours.
+        #[arg(long)]
+        a: u32,
+        /// The para id to swap with.
+        #[arg(long)]
+        b: u32,
+    },
+    /// Requests opening an HRMP channel to another parachain, through
+    /// `Hrmp::hrmp_init_open_channel`
+    HrmpOpen {
+        /// The para id receiving messages through the channel.
+        #[arg(long)]
+        recipient: u32,
+        /// The maximum number of messages that can be pending in the channel at once.
+        #[arg(long)]
+        max_capacity: u32,
+        /// The maximum size of a message sent through the channel.
+        #[arg(long)]
+        max_message_size: u32,
+    },
+    /// Accepts an HRMP channel requested by another parachain, through
+    /// `Hrmp::hrmp_accept_open_channel`
+    HrmpAccept {
+        /// The para id that requested the channel.
+        #[arg(long)]
+        sender: u32,
+    },
+    /// Closes an HRMP channel, through `Hrmp::hrmp_close_channel`
+    HrmpClose {
+        /// The para id sending messages through the channel.
+        #[arg(long)]
+        sender: u32,
+        /// The para id receiving messages through the channel.
+        #[arg(long)]
+        recipient: u32,
+    },
+}
+
+impl RelayCall {
+    /// Encodes the call against the relay chain `metadata`.
+    pub fn encode(&self, metadata: &Metadata) -> Result<Vec<u8>, subxt::Error> {
+        let para = |id: u32| Value::u128(id as u128);
+        let payload = match *self {
+            RelayCall::Lock { para: id } => {
+                subxt::dynamic::tx("Registrar", "add_lock", vec![para(id)])
+            }
+            RelayCall::Unlock { para: id } => {
+                subxt::dynamic::tx("Registrar", "remove_lock", vec![para(id)])
+            }
+            RelayCall::Swap { a, b } => {
+                subxt::dynamic::tx("Registrar", "swap", vec![para(a), para(b)])
+            }
+            RelayCall::HrmpOpen {
+                recipient,
+                max_capacity,
+                max_message_size,
+            } => subxt::dynamic::tx(
+                "Hrmp",
+                "hrmp_init_open_channel",
+                vec![
+                    para(recipient),
+                    Value::u128(max_capacity as u128),
+                    Value::u128(max_message_size as u128),
+                ],
+            ),
+            RelayCall::HrmpAccept { sender } => {
+                subxt::dynamic::tx("Hrmp", "hrmp_accept_open_channel", vec![para(sender)])
+            }
+            RelayCall::HrmpClose { sender, recipient } => subxt::dynamic::tx(
+                "Hrmp",
+                "hrmp_close_channel",
+                vec![Value::named_composite([
+                    ("sender", para(sender)),
+                    ("recipient", para(recipient)),
+                ])],
+            ),
+        };
+        payload.encode_call_data(metadata)
+    }
+}
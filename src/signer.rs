@@ -0,0 +1,366 @@
+//! Sources of the key signing the transactions
+
+use std::io::BufRead;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use base64::Engine;
+use crypto_secretbox::aead::{Aead, KeyInit};
+use crypto_secretbox::XSalsa20Poly1305;
+use serde::Deserialize;
+use subxt::tx::Signer;
+use subxt::utils::{AccountId32, MultiAddress, MultiSignature, H256};
+use subxt_signer::{sr25519, ExposeSecret, SecretUri, DEV_PHRASE};
+
+use crate::nodle::NodleConfig;
+
+/// The environment variable holding the password of `--keystore`.
+const PASSWORD_ENV: &str = "BUSYPOT_PASSWORD";
+
+/// The prefix and separator around the secret key in the pkcs8 encoding used by polkadot-js.
+const PKCS8_HEADER: [u8; 16] = [48, 83, 2, 1, 1, 48, 5, 6, 3, 43, 101, 112, 4, 34, 4, 32];
+const PKCS8_DIVIDER: [u8; 5] = [161, 35, 3, 33, 0];
+
+#[derive(Debug, clap::Args)]
+pub struct SignerArgs {
+    /// The secret uri to the private key for the signer of the transactions.
+    ///
+    /// Here is the expected format for the secret uri:
+    ///
+    /// phrase/path0/path1///password
+    ///
+    /// 111111 22222 22222   33333333
+    ///
+    /// Where:
+    ///
+    /// 1s denotes a phrase or hex string. If this is not provided, the DEV_PHRASE is used instead.
+    ///
+    /// 2s denote optional "derivation junctions" which are used to derive keys. Each of these is
+    /// separated by "/". A derivation junction beginning with "/" (ie "//" in the original string)
+    /// is a "hard" path.
+    ///
+    /// 3s denotes an optional password which is used in conjunction with the phrase provided in 1
+    /// to generate an initial key. If hex is provided for 1, it's ignored.
+    ///
+    /// Notes:
+    ///
+    /// If 1 is a 0x prefixed 64-digit hex string, then we'll interpret it as hex, and treat the hex
+    /// bytes as a seed/MiniSecretKey directly, ignoring any password.
+    ///
+    /// Else if the phrase part is a valid BIP-39 phrase, we'll use the phrase (and password, if
+    /// provided) to generate a seed/MiniSecretKey.
+    ///
+    /// Uris like "//Alice" correspond to keys derived from a DEV_PHRASE, since no phrase part is
+    /// given.
+    #[arg(short, long, default_value = "//Alice")]
+    signer: String,
+
+    /// Reads the secret uri of the signer from the environment variable VAR instead of
+    /// `--signer`, keeping it out of the shell history.
+    #[arg(long, value_name = "VAR", conflicts_with_all = ["signer", "signer_stdin", "keystore"])]
+    signer_env: Option<String>,
+
+    /// Reads the secret uri of the signer from the first line of the standard input instead of
+    /// `--signer`.
+    #[arg(long, conflicts_with_all = ["signer", "keystore"])]
+    signer_stdin: bool,
+
+    /// Signs with the sr25519 key of a polkadot-js JSON key file, as exported by the extension or
+    /// apps, instead of `--signer`.
+    ///
+    /// The password is read from the BUSYPOT_PASSWORD environment variable, or prompted for.
+    #[arg(long, conflicts_with = "signer")]
+    keystore: Option<PathBuf>,
+
+    /// The genesis hash of a development or local network, on which development keys derived
+    /// from the DEV_PHRASE like "//Alice" are allowed. Can be given several times.
+    ///
+    /// Development keys are refused on any other chain, since their secrets are public.
+    #[arg(long, value_name = "GENESIS_HASH")]
+    dev_genesis: Vec<H256>,
+
+    /// Allows signing with a development key on any chain, such as a public test network.
+    #[arg(long)]
+    allow_dev_key: bool,
+}
+
+/// The sr25519 key signing the transactions.
+pub enum Keypair {
+    /// A key derived from a secret uri, which is a development key when derived from the
+    /// DEV_PHRASE.
+    Uri {
+        keypair: sr25519::Keypair,
+        dev: bool,
+    },
+    /// A key decrypted from a polkadot-js key file.
+    Keystore(schnorrkel::Keypair),
+}
+
+impl Signer<NodleConfig> for Keypair {
+    fn account_id(&self) -> AccountId32 {
+        match self {
+            Keypair::Uri { keypair, .. } => keypair.public_key().into(),
+            Keypair::Keystore(keypair) => AccountId32(keypair.public.to_bytes()),
+        }
+    }
+
+    fn address(&self) -> MultiAddress<AccountId32, ()> {
+        self.account_id().into()
+    }
+
+    fn sign(&self, signer_payload: &[u8]) -> MultiSignature {
+        match self {
+            Keypair::Uri { keypair, .. } => keypair.sign(signer_payload).into(),
+            Keypair::Keystore(keypair) => {
+                // The same signing context as `sr25519::Keypair` and the substrate runtimes.
+                let context = schnorrkel::signing_context(b"substrate");
+                MultiSignature::Sr25519(keypair.sign(context.bytes(signer_payload)).to_bytes())
+            }
+        }
+    }
+}
+
+/// The signer given on the command line, known by its account until it first signs.
+///
+/// Key files are only decrypted, and development keys only checked, once a transaction is
+/// actually signed, so that commands that only read the chain or print their transactions
+/// neither prompt for a password nor refuse `//Alice` on a live chain.
+pub struct LazyKeypair {
+    args: SignerArgs,
+    genesis_hash: H256,
+    account: AccountId32,
+    /// The key derived from a secret uri right away, or decrypted from the key file on first use.
+    keypair: Option<Keypair>,
+    /// Whether `keypair` passed [`SignerArgs::check_dev_key`].
+    checked: bool,
+}
+
+impl LazyKeypair {
+    pub fn account_id(&self) -> AccountId32 {
+        self.account.clone()
+    }
+
+    /// Loads the key if needed and checks it, once.
+    pub fn load(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.checked {
+            return Ok(());
+        }
+        let keypair = match self.keypair.take() {
+            Some(keypair) => keypair,
+            None => self.args.keypair()?,
+        };
+        self.args.check_dev_key(&keypair, self.genesis_hash)?;
+        self.keypair = Some(keypair);
+        self.checked = true;
+        Ok(())
+    }
+
+    /// The key, which must have been loaded with [`LazyKeypair::load`].
+    pub fn keypair(&self) -> &Keypair {
+        match &self.keypair {
+            Some(keypair) if self.checked => keypair,
+            _ => panic!("the signer is loaded before signing"),
+        }
+    }
+}
+
+impl SignerArgs {
+    /// Loads the signer from the source given on the command line.
+    pub fn keypair(&self) -> Result<Keypair, Box<dyn std::error::Error>> {
+        if let Some(path) = &self.keystore {
+            let password = match std::env::var(PASSWORD_ENV) {
+                Ok(password) => password,
+                Err(_) => rpassword::prompt_password(format!("password of {}: ", path.display()))?,
+            };
+            let keypair = decrypt_keystore(&std::fs::read_to_string(path)?, &password)?;
+            return Ok(Keypair::Keystore(keypair));
+        }
+        self.uri_keypair()
+    }
+
+    /// Defers loading and checking the signer until it signs on the chain with `genesis_hash`, see
+    /// [`LazyKeypair`]. Only the account of a key file is read for now.
+    pub fn lazy(self, genesis_hash: H256) -> Result<LazyKeypair, Box<dyn std::error::Error>> {
+        let (account, keypair) = match &self.keystore {
+            Some(path) => {
+                let file: KeystoreFile = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+                (AccountId32::from_str(&file.address)?, None)
+            }
+            None => {
+                let keypair = self.uri_keypair()?;
+                (keypair.account_id(), Some(keypair))
+            }
+        };
+        Ok(LazyKeypair {
+            args: self,
+            genesis_hash,
+            account,
+            keypair,
+            checked: false,
+        })
+    }
+
+    /// Derives the signer from its secret uri, given directly, in an environment variable or on
+    /// the standard input.
+    fn uri_keypair(&self) -> Result<Keypair, Box<dyn std::error::Error>> {
+        let secret_uri = if let Some(var) = &self.signer_env {
+            std::env::var(var).map_err(|_| format!("the environment variable {var} is not set"))?
+        } else if self.signer_stdin {
+            let mut line = String::new();
+            std::io::stdin().lock().read_line(&mut line)?;
+            line.trim().to_string()
+        } else {
+            self.signer.clone()
+        };
+        let secret_uri = SecretUri::from_str(&secret_uri)?;
+        Ok(Keypair::Uri {
+            dev: secret_uri.phrase.expose_secret() == DEV_PHRASE,
+            keypair: sr25519::Keypair::from_uri(&secret_uri)?,
+        })
+    }
+
+    /// Refuses development keys unless `genesis_hash`, the genesis hash of the chain, is one of
+    /// `--dev-genesis` or `--allow-dev-key` is set. What the node says about its chain is not
+    /// trusted, since any chain spec can call itself a development network.
+    pub fn check_dev_key(&self, keypair: &Keypair, genesis_hash: H256) -> Result<(), String> {
+        if !matches!(keypair, Keypair::Uri { dev: true, .. })
+            || self.allow_dev_key
+            || self.dev_genesis.contains(&genesis_hash)
+        {
+            return Ok(());
+        }
+        Err(format!(
+            "refusing to sign with a development key on the chain with genesis hash \
+             {genesis_hash:?}, use --keystore, --signer-env or --signer-stdin for a real key, or \
+             --dev-genesis {genesis_hash:?} if it is a development or local network"
+        ))
+    }
+}
+
+#[derive(Deserialize)]
+struct KeystoreFile {
+    encoded: String,
+    encoding: KeystoreEncoding,
+    address: String,
+}
+
+#[derive(Deserialize)]
+struct KeystoreEncoding {
+    content: Vec<String>,
+    #[serde(rename = "type")]
+    kind: Vec<String>,
+}
+
+/// Decrypts the sr25519 key of a polkadot-js JSON key file with `password`.
+fn decrypt_keystore(
+    json: &str,
+    password: &str,
+) -> Result<schnorrkel::Keypair, Box<dyn std::error::Error>> {
+    let file: KeystoreFile = serde_json::from_str(json)?;
+    if !file.encoding.content.iter().any(|c| c == "sr25519") {
+        return Err(format!(
+            "only sr25519 key files are supported, not {:?}",
+            file.encoding.content
+        )
+        .into());
+    }
+    let encoded = base64::engine::general_purpose::STANDARD.decode(file.encoded.trim())?;
+
+    // Recent key files derive the encryption key from the password with scrypt, whose salt and
+    // parameters come first. Older ones use the password padded with zeros.
+    let (key, encrypted) = if file.encoding.kind.iter().any(|t| t == "scrypt") {
+        if encoded.len() < 44 {
+            return Err("the key file is truncated".into());
+        }
+        let (salt, rest) = encoded.split_at(32);
+        let param = |i: usize| u32::from_le_bytes(rest[i * 4..i * 4 + 4].try_into().unwrap());
+        let (n, p, r) = (param(0), param(1), param(2));
+        if !n.is_power_of_two() {
+            return Err(format!("invalid scrypt parameter N = {n}").into());
+        }
+        let params = scrypt::Params::new(n.trailing_zeros() as u8, r, p, 32)
+            .map_err(|e| format!("invalid scrypt parameters: {e}"))?;
+        let mut key = [0u8; 32];
+        scrypt::scrypt(password.as_bytes(), salt, &params, &mut key)
+            .map_err(|e| format!("failed to derive the key: {e}"))?;
+        (key, &rest[12..])
+    } else {
+        let mut key = [0u8; 32];
+        let len = password.len().min(32);
+        key[..len].copy_from_slice(&password.as_bytes()[..len]);
+        (key, &encoded[..])
+    };
+
+    if encrypted.len() < 24 {
+        return Err("the key file is truncated".into());
+    }
+    let (nonce, ciphertext) = encrypted.split_at(24);
+    let pkcs8 = XSalsa20Poly1305::new(&key.into())
+        .decrypt(nonce.into(), ciphertext)
+        .map_err(|_| "failed to decrypt the key file, is the password correct?")?;
+
+    let expected_len = PKCS8_HEADER.len() + 64 + PKCS8_DIVIDER.len() + 32;
+    if pkcs8.len() != expected_len || pkcs8[..16] != PKCS8_HEADER || pkcs8[80..85] != PKCS8_DIVIDER
+    {
+        return Err("the decrypted key is not in the expected pkcs8 format".into());
+    }
+    let secret = schnorrkel::SecretKey::from_ed25519_bytes(&pkcs8[16..80])
+        .map_err(|e| format!("invalid secret key: {e}"))?;
+    let keypair = secret.to_keypair();
+
+    let address = AccountId32::from_str(&file.address)?;
+    if keypair.public.to_bytes() != pkcs8[85..] || keypair.public.to_bytes() != address.0 {
+        return Err("the decrypted key does not match the address of the key file".into());
+    }
+    Ok(keypair)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// //Alice in the polkadot-js key file format (scrypt, N = 32768, p = 1, r = 8), with the
+    /// password "busypot".
+    const ALICE_KEYSTORE: &str = include_str!("../testdata/alice-keystore.json");
+    const ALICE: &str = "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY";
+
+    #[test]
+    fn decrypts_keystore() {
+        let keypair = decrypt_keystore(ALICE_KEYSTORE, "busypot").unwrap();
+        assert_eq!(AccountId32(keypair.public.to_bytes()).to_string(), ALICE);
+    }
+
+    #[test]
+    fn refuses_dev_keys_outside_dev_networks() {
+        #[derive(clap::Parser)]
+        struct Cli {
+            #[command(flatten)]
+            signer: SignerArgs,
+        }
+        let parse =
+            |args: &[&str]| <Cli as clap::Parser>::parse_from([&["busypot"], args].concat()).signer;
+        let dev_genesis = H256::repeat_byte(1);
+        let other_genesis = H256::repeat_byte(2);
+
+        let args = parse(&["--dev-genesis", &format!("{dev_genesis:?}")]);
+        let alice = args.keypair().unwrap();
+        assert!(args.check_dev_key(&alice, dev_genesis).is_ok());
+        assert!(args.check_dev_key(&alice, other_genesis).is_err());
+
+        let args = parse(&["--allow-dev-key"]);
+        assert!(args.check_dev_key(&alice, other_genesis).is_ok());
+
+        let args = parse(&["--signer", &format!("0x{}", "11".repeat(32))]);
+        let keypair = args.keypair().unwrap();
+        assert!(args.check_dev_key(&keypair, other_genesis).is_ok());
+    }
+
+    #[test]
+    fn rejects_wrong_password() {
+        let error = decrypt_keystore(ALICE_KEYSTORE, "not busypot").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "failed to decrypt the key file, is the password correct?"
+        );
+    }
+}
@@ -0,0 +1,339 @@
+//! Creating, removing and reading the pots and users of `pallet_sponsorship`
+
+use std::fmt;
+use std::ops::Range;
+
+use clap::ValueEnum;
+use subxt::utils::AccountId32;
+use subxt::OnlineClient;
+
+use crate::amount::format_units;
+use crate::eden::{
+    self,
+    runtime_types::{pallet_sponsorship::PotDetails, runtime_eden::pallets_util::SponsorshipType},
+};
+use crate::journal::{Journal, Unit};
+use crate::nodle::{NodleConfig, TOKEN_DECIMALS};
+use crate::tx::{EncodedCall, Sender};
+use crate::users::User;
+
+/// A pot as stored in `Sponsorship::Pot`.
+pub type Pot = PotDetails<AccountId32, SponsorshipType, u128>;
+
+/// The most users registered or removed by a single `register_users` or `remove_users` call.
+const MAX_USERS_ONE_BLOCK: usize = 500;
+
+/// The sponsorship types of the runtime, deciding which calls the users of a pot can have
+/// sponsored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SponsorshipKind {
+    /// Any call that cannot move funds out of the proxy account of the user.
+    AnySafe,
+    /// Only the calls of `Uniques`.
+    Uniques,
+}
+
+impl From<SponsorshipKind> for SponsorshipType {
+    fn from(kind: SponsorshipKind) -> Self {
+        match kind {
+            SponsorshipKind::AnySafe => SponsorshipType::AnySafe,
+            SponsorshipKind::Uniques => SponsorshipType::Uniques,
+        }
+    }
+}
+
+impl From<&SponsorshipType> for SponsorshipKind {
+    fn from(sponsorship_type: &SponsorshipType) -> Self {
+        match sponsorship_type {
+            SponsorshipType::AnySafe => SponsorshipKind::AnySafe,
+            SponsorshipType::Uniques => SponsorshipKind::Uniques,
+        }
+    }
+}
+
+impl fmt::Display for SponsorshipKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SponsorshipKind::AnySafe => write!(f, "any-safe"),
+            SponsorshipKind::Uniques => write!(f, "uniques"),
+        }
+    }
+}
+
+/// The sponsorship type and limits of the pots created by `create-pots`, in the smallest unit of
+/// NODL.
+pub struct PotLimits {
+    pub sponsorship_type: SponsorshipType,
+    pub fee_quota: u128,
+    pub reserve_quota: u128,
+}
+
+impl PotLimits {
+    /// Tells whether the limits of `pot` are not these ones, then whether its sponsorship type
+    /// is not this one.
+    fn differences(&self, pot: &Pot) -> (bool, bool) {
+        (
+            pot.fee_quota.limit != self.fee_quota || pot.reserve_quota.limit != self.reserve_quota,
+            pot.sponsorship_type != self.sponsorship_type,
+        )
+    }
+}
+
+/// Creates the pots with ids in `ids` with `limits`, skipping those that already exist so that
+/// a run can be repeated.
+///
+/// With `update_existing`, the existing pots sponsored by the origin of the sender get their
+/// limits and sponsorship type updated where they differ from `limits`. Other existing pots are
+/// only reported.
+pub async fn create_pots(
+    api: &OnlineClient<NodleConfig>,
+    sender: &mut Sender,
+    mut journal: Option<&mut Journal>,
+    ids: Range<u32>,
+    limits: &PotLimits,
+    update_existing: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let origin = sender.origin();
+    let pots = ids.len();
+    let (mut units, mut create_pots) = (Vec::new(), Vec::new());
+    let (mut update_units, mut updates) = (Vec::new(), Vec::new());
+    for id in ids {
+        let unit = Unit::Pot { id };
+        let Some(pot) = pot(api, id).await? else {
+            if let Some(journal) = &journal {
+                journal.ensure_not_in_pool(&unit)?;
+            }
+            println!("Creating pot {id}/{pots}");
+            let create_pot = eden::tx().sponsorship().create_pot(
+                id,
+                limits.sponsorship_type.clone(),
+                limits.fee_quota,
+                limits.reserve_quota,
+            );
+            create_pots.push(EncodedCall(api.tx().call_data(&create_pot)?));
+            units.push(unit);
+            continue;
+        };
+
+        if let Some(journal) = &mut journal {
+            journal.found(&unit)?;
+        }
+        if pot.sponsor != origin {
+            println!("Pot {id} already exists, sponsored by {}", pot.sponsor);
+            continue;
+        }
+        let (limits_differ, type_differs) = limits.differences(&pot);
+        if !limits_differ && !type_differs {
+            println!("Pot {id} already exists");
+        } else if !update_existing {
+            println!(
+                "Pot {id} already exists as {} with a fee quota of {} NODL and a reserve quota of \
+                 {} NODL, which --update-existing changes",
+                SponsorshipKind::from(&pot.sponsorship_type),
+                format_units(pot.fee_quota.limit, TOKEN_DECIMALS),
+                format_units(pot.reserve_quota.limit, TOKEN_DECIMALS)
+            );
+        } else {
+            println!("Updating pot {id}");
+            if limits_differ {
+                let update_limits = eden::tx().sponsorship().update_pot_limits(
+                    id,
+                    limits.fee_quota,
+                    limits.reserve_quota,
+                );
+                updates.push(EncodedCall(api.tx().call_data(&update_limits)?));
+                update_units.push(unit.clone());
+            }
+            if type_differs {
+                let update_type = eden::tx()
+                    .sponsorship()
+                    .update_sponsorship_type(id, limits.sponsorship_type.clone());
+                updates.push(EncodedCall(api.tx().call_data(&update_type)?));
+                update_units.push(unit);
+            }
+        }
+    }
+
+    // The creations and updates are sent together, so that their fees are previewed and capped
+    // by --max-total-fee as a whole and what became of each of them is reported at once.
+    create_pots.extend(updates);
+    units.extend(update_units);
+    sender.send_journaled(&create_pots, &units, journal).await?;
+    Ok(())
+}
+
+/// A user to register in a pot, with the limits it gets from the pot in the smallest unit of
+/// NODL.
+pub struct NewUser {
+    /// The id the user is known by in the run, such as the index its address is derived from.
+    pub id: u32,
+    pub account: AccountId32,
+    pub fee_quota: u128,
+    pub reserve_quota: u128,
+}
+
+/// Registers `users` in the pot `pot_id`.
+///
+/// Consecutive users with the same limits are registered together, up to `MAX_USERS_ONE_BLOCK`
+/// per call, so users sharing limits are best kept next to each other.
+pub async fn register_users(
+    api: &OnlineClient<NodleConfig>,
+    sender: &mut Sender,
+    mut journal: Option<&mut Journal>,
+    pot_id: u32,
+    users: &[NewUser],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let total = users.len();
+    let mut units = Vec::new();
+    let mut register_users = Vec::new();
+    let chunks = users
+        .chunk_by(|a, b| (a.fee_quota, a.reserve_quota) == (b.fee_quota, b.reserve_quota))
+        .flat_map(|same_limits| same_limits.chunks(MAX_USERS_ONE_BLOCK));
+    for chunk in chunks {
+        let accounts = chunk
+            .iter()
+            .map(|user| user.account.clone())
+            .collect::<Vec<_>>();
+        let unit = Unit::Users {
+            pot_id,
+            first: chunk[0].id,
+            last: chunk[chunk.len() - 1].id,
+        };
+        if let Some(journal) = &mut journal {
+            if !journal
+                .needs_sending(&unit, users_registered(api, pot_id, &accounts))
+                .await?
+            {
+                println!("The {unit} were already registered");
+                continue;
+            }
+        }
+        println!(
+            "Registering {chunk_len} users / {total} with a fee quota of {} NODL and a reserve \
+             quota of {} NODL",
+            format_units(chunk[0].fee_quota, TOKEN_DECIMALS),
+            format_units(chunk[0].reserve_quota, TOKEN_DECIMALS),
+            chunk_len = chunk.len()
+        );
+        register_users.push(eden::tx().sponsorship().register_users(
+            pot_id,
+            accounts,
+            chunk[0].fee_quota,
+            chunk[0].reserve_quota,
+        ));
+        units.push(unit);
+    }
+    sender
+        .send_journaled(&register_users, &units, journal)
+        .await?;
+    Ok(())
+}
+
+/// Removes `users` from the pot `pot_id`, which the origin of the sender must sponsor, up to
+/// `MAX_USERS_ONE_BLOCK` per call. Users that are not registered in the pot are skipped.
+pub async fn remove_users(
+    api: &OnlineClient<NodleConfig>,
+    sender: &mut Sender,
+    pot_id: u32,
+    users: &[User],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let pot = pot(api, pot_id)
+        .await?
+        .ok_or_else(|| format!("pot {pot_id} does not exist"))?;
+    let origin = sender.origin();
+    if pot.sponsor != origin {
+        return Err(format!("pot {pot_id} is sponsored by {}, not {origin}", pot.sponsor).into());
+    }
+
+    let storage = api.storage().at_latest().await?;
+    let mut registered = Vec::with_capacity(users.len());
+    for user in users {
+        let user_query = eden::storage().sponsorship().user(pot_id, &user.account);
+        if storage.fetch(&user_query).await?.is_some() {
+            registered.push(user.account.clone());
+        } else {
+            println!("User {} is not registered in pot {pot_id}", user.id);
+        }
+    }
+
+    let total = registered.len();
+    let mut remove_users = Vec::new();
+    for chunk in registered.chunks(MAX_USERS_ONE_BLOCK) {
+        println!(
+            "Removing {chunk_len} users / {total}",
+            chunk_len = chunk.len()
+        );
+        remove_users.push(
+            eden::tx()
+                .sponsorship()
+                .remove_users(pot_id, chunk.to_vec()),
+        );
+    }
+    sender.send_all(&remove_users).await?;
+    Ok(())
+}
+
+/// Removes the pots with ids in `ids`, after making sure that none of them still has users.
+/// Pots that do not exist or are sponsored by another account than the origin of the sender are
+/// skipped.
+pub async fn remove_pots(
+    api: &OnlineClient<NodleConfig>,
+    sender: &mut Sender,
+    ids: Range<u32>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let origin = sender.origin();
+    let storage = api.storage().at_latest().await?;
+    let mut remove_pots = Vec::new();
+    let mut with_users = Vec::new();
+    for id in ids {
+        let Some(pot) = pot(api, id).await? else {
+            println!("Pot {id} does not exist");
+            continue;
+        };
+        if pot.sponsor != origin {
+            println!("Pot {id} is sponsored by {}, skipped", pot.sponsor);
+            continue;
+        }
+        let users_query = eden::storage().sponsorship().user_iter1(id);
+        if storage.iter(users_query).await?.next().await.is_some() {
+            with_users.push(id.to_string());
+            continue;
+        }
+        println!("Removing pot {id}");
+        remove_pots.push(eden::tx().sponsorship().remove_pot(id));
+    }
+    if !with_users.is_empty() {
+        return Err(format!(
+            "pot(s) {} still have users, remove them with remove-users first",
+            with_users.join(", ")
+        )
+        .into());
+    }
+    sender.send_all(&remove_pots).await?;
+    Ok(())
+}
+
+/// Reads the pot `pot_id` in the latest finalized block, if it exists.
+pub async fn pot(
+    api: &OnlineClient<NodleConfig>,
+    pot_id: u32,
+) -> Result<Option<Pot>, subxt::Error> {
+    let pot_query = eden::storage().sponsorship().pot(pot_id);
+    api.storage().at_latest().await?.fetch(&pot_query).await
+}
+
+/// Tells whether all of `users` are registered in the pot `pot_id` in the latest finalized block.
+pub async fn users_registered(
+    api: &OnlineClient<NodleConfig>,
+    pot_id: u32,
+    users: &[AccountId32],
+) -> Result<bool, subxt::Error> {
+    let storage = api.storage().at_latest().await?;
+    for user in users {
+        let user_query = eden::storage().sponsorship().user(pot_id, user);
+        if storage.fetch(&user_query).await?.is_none() {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
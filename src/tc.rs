@@ -0,0 +1,387 @@
+//! Commands for taking part in the technical committee once a proposal is open
+
+use std::fmt;
+use std::str::FromStr;
+
+use clap::{ArgGroup, Subcommand};
+use codec::{DecodeAll, Encode};
+use subxt::blocks::ExtrinsicEvents;
+use subxt::ext::scale_value::{Value, ValueDef};
+use subxt::tx::TxPayload;
+use subxt::utils::{AccountId32, H256};
+use subxt::{Metadata, OnlineClient};
+
+use crate::eden::{
+    self,
+    runtime_types::{
+        pallet_mandate::pallet::Call::apply,
+        pallet_multisig::pallet::Call::{as_multi, as_multi_threshold_1},
+        pallet_xcm::pallet::Call::send,
+        runtime_eden::RuntimeCall,
+    },
+    technical_committee::events,
+};
+use crate::nodle::NodleConfig;
+use crate::tx::Sender;
+use crate::{payment, xcm};
+
+#[derive(Debug, Subcommand)]
+pub enum TcCommand {
+    /// Lists the open technical committee proposals along with their votes
+    Proposals,
+    /// Votes on an open technical committee proposal
+    #[command(group(ArgGroup::new("decision").required(true).args(["approve", "reject"])))]
+    Vote {
+        /// The hash of the proposal to vote on.
+        #[arg(long)]
+        hash: H256,
+        /// The index of the proposal. If not provided, it is read from `TechnicalCommittee::Voting`.
+        #[arg(long)]
+        index: Option<u32>,
+        /// Vote in favour of the proposal.
+        #[arg(long)]
+        approve: bool,
+        /// Vote against the proposal.
+        #[arg(long)]
+        reject: bool,
+    },
+    /// Closes a technical committee proposal that is either approved, disapproved or whose voting
+    /// period has ended
+    Close {
+        /// The hash of the proposal to close.
+        #[arg(long)]
+        hash: H256,
+        /// The index of the proposal. If not provided, it is read from `TechnicalCommittee::Voting`.
+        #[arg(long)]
+        index: Option<u32>,
+    },
+}
+
+/// The number of committee members that need to approve a proposal for it to pass.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Threshold {
+    /// More than half of the members.
+    SimpleMajority,
+    /// At least two thirds of the members.
+    TwoThirds,
+    /// All of the members.
+    Unanimous,
+    /// An explicit number of members.
+    Exact(u32),
+}
+
+impl Threshold {
+    /// Computes the threshold for a committee of `members` members.
+    pub fn resolve(&self, members: usize) -> Result<u32, String> {
+        let members = members as u32;
+        let threshold = match *self {
+            Threshold::SimpleMajority => members / 2 + 1,
+            Threshold::TwoThirds => (2 * members).div_ceil(3),
+            Threshold::Unanimous => members,
+            Threshold::Exact(threshold) => threshold,
+        };
+        if threshold == 0 || threshold > members {
+            return Err(format!(
+                "threshold {threshold} is invalid for a committee of {members} members"
+            ));
+        }
+        Ok(threshold)
+    }
+}
+
+impl FromStr for Threshold {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "simple-majority" => Ok(Threshold::SimpleMajority),
+            "two-thirds" => Ok(Threshold::TwoThirds),
+            "unanimous" => Ok(Threshold::Unanimous),
+            _ => s.parse().map(Threshold::Exact).map_err(|_| {
+                format!(
+                    "invalid threshold `{s}`, expected one of: simple-majority, two-thirds, \
+                     unanimous or a number of members"
+                )
+            }),
+        }
+    }
+}
+
+impl fmt::Display for Threshold {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Threshold::SimpleMajority => write!(f, "simple-majority"),
+            Threshold::TwoThirds => write!(f, "two-thirds"),
+            Threshold::Unanimous => write!(f, "unanimous"),
+            Threshold::Exact(threshold) => write!(f, "{threshold}"),
+        }
+    }
+}
+
+/// Reads the members of the technical committee, failing if there are none.
+pub async fn members(
+    api: &OnlineClient<NodleConfig>,
+) -> Result<Vec<AccountId32>, Box<dyn std::error::Error>> {
+    let members_query = eden::storage().technical_membership().members();
+    let members = api
+        .storage()
+        .at_latest()
+        .await?
+        .fetch_or_default(&members_query)
+        .await?
+        .0;
+    if members.is_empty() {
+        return Err("the technical committee has no members".into());
+    }
+    Ok(members)
+}
+
+/// Parses a call of our parachain given either as scale encoded hex or as JSON of the form
+/// `{"pallet": "...", "call": "...", "args": {...}}`, making sure it is a valid `RuntimeCall`.
+pub fn parse_local_call(
+    metadata: &Metadata,
+    input: &str,
+) -> Result<RuntimeCall, Box<dyn std::error::Error>> {
+    let input = input.trim();
+    let encoded = if input.starts_with('{') {
+        let json: serde_json::Value = serde_json::from_str(input)?;
+        let field = |name: &str| {
+            json.get(name)
+                .and_then(|v| v.as_str())
+                .ok_or_else(|| format!("the `{name}` field of the call is missing"))
+        };
+        let args: Value = match json.get("args") {
+            Some(args) => serde_json::from_value(args.clone())?,
+            None => Value::unnamed_composite([]),
+        };
+        let ValueDef::Composite(args) = args.value else {
+            return Err("the `args` field of the call must be an object or an array".into());
+        };
+        subxt::dynamic::tx(field("pallet")?, field("call")?, args).encode_call_data(metadata)?
+    } else {
+        hex::decode(input.trim_start_matches("0x"))?
+    };
+
+    Ok(RuntimeCall::decode_all(&mut &encoded[..])?)
+}
+
+/// Proposes `call` to the technical committee, to be dispatched as root through `Mandate::apply`
+/// once approved. With `dry_run`, the proposal is printed instead of being submitted.
+pub async fn propose(
+    api: &OnlineClient<NodleConfig>,
+    sender: &mut Sender,
+    url: &str,
+    call: RuntimeCall,
+    threshold: Threshold,
+    dry_run: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let technical_committee_call = RuntimeCall::Mandate(apply { call: call.into() });
+
+    let members = members(api).await?;
+    let proposer = sender.origin();
+    if !dry_run && !members.contains(&proposer) {
+        return Err(format!("{proposer} is not a member of the technical committee").into());
+    }
+    let threshold = threshold.resolve(members.len())?;
+    println!("using tech committee threshold: {}", threshold);
+
+    let length_bound = technical_committee_call.encoded_size() as u32;
+
+    let technical_committee =
+        eden::tx()
+            .technical_committee()
+            .propose(threshold, technical_committee_call, length_bound);
+
+    if dry_run {
+        let mocked = api.tx().call_data(&technical_committee)?;
+        let mocked = format!("0x{}", hex::encode(mocked));
+
+        println!("final extrinsic: {}", mocked);
+        println!(
+            "shortlink: https://nodleprotocol.io/?rpc={}#/extrinsics/decode/{}",
+            urlencoding::encode(url),
+            mocked
+        );
+    } else if let Some(events) = sender.send(&technical_committee).await? {
+        println!("events: {events:?}");
+    }
+
+    Ok(())
+}
+
+/// Reads the index of the open proposal with `hash`, making sure it matches `index` if provided.
+async fn proposal_index(
+    api: &OnlineClient<NodleConfig>,
+    hash: H256,
+    index: Option<u32>,
+) -> Result<u32, Box<dyn std::error::Error>> {
+    let voting_query = eden::storage().technical_committee().voting(hash);
+    let voting = api
+        .storage()
+        .at_latest()
+        .await?
+        .fetch(&voting_query)
+        .await?
+        .ok_or_else(|| format!("no open proposal with hash {hash:?}"))?;
+    println!(
+        "proposal #{}: {} ayes, {} nays, threshold {}, ends at block {}",
+        voting.index,
+        voting.ayes.len(),
+        voting.nays.len(),
+        voting.threshold,
+        voting.end
+    );
+
+    match index {
+        Some(index) if index != voting.index => {
+            Err(format!("proposal {hash:?} has index {}, not {index}", voting.index).into())
+        }
+        _ => Ok(voting.index),
+    }
+}
+
+/// Prints `call` prefixed by `indent`, unwrapping the calls wrapped by `Mandate::apply` and
+/// `Multisig::as_multi`, and the xcm messages sent by `PolkadotXcm::send`.
+pub fn print_call(call: &RuntimeCall, indent: &str) {
+    match call {
+        RuntimeCall::Mandate(apply { call }) => {
+            println!("{indent}Mandate::apply");
+            print_call(call, &format!("{indent}    "));
+        }
+        RuntimeCall::Multisig(as_multi {
+            threshold,
+            maybe_timepoint,
+            call,
+            ..
+        }) => {
+            println!("{indent}Multisig::as_multi with threshold {threshold}, timepoint {maybe_timepoint:?}");
+            print_call(call, &format!("{indent}    "));
+        }
+        RuntimeCall::Multisig(as_multi_threshold_1 { call, .. }) => {
+            println!("{indent}Multisig::as_multi_threshold_1");
+            print_call(call, &format!("{indent}    "));
+        }
+        RuntimeCall::PolkadotXcm(send { dest, message }) => {
+            println!("{indent}PolkadotXcm::send to {dest:?}");
+            xcm::print_message(message, &format!("{indent}    "));
+        }
+        other => println!("{indent}{other:?}"),
+    }
+}
+
+/// Prints every open proposal with its call and votes.
+async fn list_proposals(api: &OnlineClient<NodleConfig>) -> Result<(), Box<dyn std::error::Error>> {
+    let storage = api.storage().at_latest().await?;
+    let committee = eden::storage().technical_committee();
+
+    let proposals = storage.fetch_or_default(&committee.proposals()).await?.0;
+    println!("{} open proposal(s)", proposals.len());
+
+    for hash in proposals {
+        println!();
+        println!("hash: {hash:?}");
+        if let Some(voting) = storage.fetch(&committee.voting(hash)).await? {
+            println!("index: {}", voting.index);
+            println!("threshold: {}", voting.threshold);
+            println!("ayes ({}): {:?}", voting.ayes.len(), voting.ayes);
+            println!("nays ({}): {:?}", voting.nays.len(), voting.nays);
+            println!("end: {}", voting.end);
+        }
+        match storage.fetch(&committee.proposal_of(hash)).await? {
+            Some(call) => {
+                println!("call:");
+                print_call(&call, "    ");
+            }
+            None => println!("call: unknown"),
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints the technical committee events emitted by a vote or a close.
+fn report_events(events: &ExtrinsicEvents<NodleConfig>) -> Result<(), subxt::Error> {
+    for voted in events.find::<events::Voted>() {
+        let voted = voted?;
+        println!(
+            "voted: {} ({} ayes, {} nays)",
+            if voted.voted { "aye" } else { "nay" },
+            voted.yes,
+            voted.no
+        );
+    }
+    for approved in events.find::<events::Approved>() {
+        println!("approved: {:?}", approved?.proposal_hash);
+    }
+    for disapproved in events.find::<events::Disapproved>() {
+        println!("disapproved: {:?}", disapproved?.proposal_hash);
+    }
+    for executed in events.find::<events::Executed>() {
+        let executed = executed?;
+        println!(
+            "executed: {:?} with result {:?}",
+            executed.proposal_hash, executed.result
+        );
+    }
+    for closed in events.find::<events::Closed>() {
+        let closed = closed?;
+        println!(
+            "closed: {:?} ({} ayes, {} nays)",
+            closed.proposal_hash, closed.yes, closed.no
+        );
+    }
+    Ok(())
+}
+
+pub async fn run(
+    api: &OnlineClient<NodleConfig>,
+    sender: &mut Sender,
+    command: TcCommand,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let events = match command {
+        TcCommand::Proposals => return list_proposals(api).await,
+        TcCommand::Vote {
+            hash,
+            index,
+            approve,
+            reject: _,
+        } => {
+            let index = proposal_index(api, hash, index).await?;
+            let vote = eden::tx().technical_committee().vote(hash, index, approve);
+            sender.send(&vote).await?
+        }
+        TcCommand::Close { hash, index } => {
+            let index = proposal_index(api, hash, index).await?;
+
+            let proposal_query = eden::storage().technical_committee().proposal_of(hash);
+            let proposal = api
+                .storage()
+                .at_latest()
+                .await?
+                .fetch(&proposal_query)
+                .await?
+                .ok_or_else(|| format!("proposal {hash:?} not found"))?
+                .encode();
+
+            let proposal_weight_bound = payment::query_call_info(api, &proposal).await?.weight;
+            let length_bound = proposal.len() as u32;
+            println!(
+                "closing with weight bound ref_time: {}, proof_size: {} and length bound {length_bound}",
+                proposal_weight_bound.ref_time, proposal_weight_bound.proof_size
+            );
+
+            let close = eden::tx().technical_committee().close(
+                hash,
+                index,
+                proposal_weight_bound,
+                length_bound,
+            );
+            sender.send(&close).await?
+        }
+    };
+
+    if let Some(events) = events {
+        report_events(&events)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,800 @@
+//! Signing and submission of the transactions composed by the commands
+
+use std::collections::{BTreeSet, VecDeque};
+use std::fmt;
+use std::path::PathBuf;
+
+use subxt::backend::legacy::LegacyRpcMethods;
+use subxt::blocks::ExtrinsicEvents;
+use subxt::config::{Config, ExtrinsicParams, Header};
+use subxt::error::{RpcError, TransactionError};
+use subxt::tx::{
+    SubmittableExtrinsic, TransactionInvalid, TxPayload, TxProgress, TxStatus, ValidationResult,
+};
+use subxt::utils::{AccountId32, H256};
+use subxt::{Metadata, OnlineClient};
+
+use crate::amount::{format_units, TokenAmount};
+use crate::eden;
+use crate::journal::{Entry, Journal, Status, Unit};
+use crate::multisig::MultisigAccount;
+use crate::nodle::{
+    NodleConfig, NodleExtrinsicParams, NodleExtrinsicParamsBuilder, TOKEN_DECIMALS,
+};
+use crate::offline::{self, UnsignedPayload, UnsignedTransaction};
+use crate::payment;
+use crate::signer::LazyKeypair;
+
+/// A transaction signed for our parachain and ready to be submitted.
+pub type Extrinsic = SubmittableExtrinsic<NodleConfig, OnlineClient<NodleConfig>>;
+
+/// The extrinsic parameters built by [`NodleExtrinsicParamsBuilder`].
+pub type Params = <NodleExtrinsicParams<NodleConfig> as ExtrinsicParams<NodleConfig>>::Params;
+
+type NodleHeader = <NodleConfig as Config>::Header;
+
+#[derive(Debug, Clone, clap::Args)]
+pub struct TxArgs {
+    /// The number of blocks the transactions stay valid for once signed, rounded up to a power of
+    /// two. Transactions that expire before being included are signed again.
+    ///
+    /// With `build`, the transactions must be signed and submitted within this period.
+    #[arg(long, default_value_t = 64, conflicts_with = "immortal")]
+    mortality: u64,
+
+    /// Makes the transactions valid forever rather than for `--mortality` blocks, which allows
+    /// them to be replayed if the account is ever reaped and its nonce reset.
+    #[arg(long)]
+    immortal: bool,
+
+    /// The tip given to the block author with every transaction, in NODL.
+    #[arg(long, default_value = "0")]
+    tip: TokenAmount,
+
+    /// The most that the transactions sent by a command may cost in fees and tips together, in
+    /// NODL. Their fees are estimated before sending them, and nothing is sent if they would go
+    /// over it.
+    #[arg(long)]
+    max_total_fee: Option<TokenAmount>,
+
+    /// The most transactions sent and waiting to be included at once. More are only sent as
+    /// earlier ones go through, so that large runs do not overflow the transaction pool.
+    #[arg(long, default_value_t = 256, value_parser = clap::value_parser!(u64).range(1..))]
+    max_in_flight: u64,
+
+    /// How far the transactions are waited for before they count as done.
+    ///
+    /// "finalized" waits for them to be in a finalized block, "in-block" only for them to be in a
+    /// block, which may still be retracted, and "none" stops once the last ones are sent without
+    /// checking whether their calls succeeded.
+    #[arg(long, value_enum, default_value_t = WaitFor::Finalized)]
+    wait_for: WaitFor,
+}
+
+/// How far `--wait-for` waits for the transactions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum WaitFor {
+    InBlock,
+    Finalized,
+    None,
+}
+
+/// A call that is already scale encoded, such as one wrapped for a multisig or calls of different
+/// kinds sent together.
+pub struct EncodedCall(pub Vec<u8>);
+
+impl TxPayload for EncodedCall {
+    fn encode_call_data_to(
+        &self,
+        _metadata: &Metadata,
+        out: &mut Vec<u8>,
+    ) -> Result<(), subxt::Error> {
+        out.extend_from_slice(&self.0);
+        Ok(())
+    }
+}
+
+/// Signs and submits the transactions composed by the commands, or collects them unsigned for
+/// signing on an offline machine.
+pub struct Sender {
+    api: OnlineClient<NodleConfig>,
+    rpc: LegacyRpcMethods<NodleConfig>,
+    args: TxArgs,
+    account: AccountId32,
+    nonce: u64,
+    mode: Mode,
+    multisig: Option<MultisigAccount>,
+    fees: Fees,
+}
+
+/// The tip and fee limit of the transactions in the smallest unit of NODL, along with the fees of
+/// the transactions sent so far.
+struct Fees {
+    tip: u128,
+    max_total: Option<u128>,
+    total: u128,
+}
+
+impl Fees {
+    fn new(args: &TxArgs) -> Result<Self, String> {
+        Ok(Fees {
+            tip: args.tip.to_units(TOKEN_DECIMALS)?,
+            max_total: args
+                .max_total_fee
+                .as_ref()
+                .map(|max| max.to_units(TOKEN_DECIMALS))
+                .transpose()?,
+            total: 0,
+        })
+    }
+
+    /// Estimates the fee of every transaction, given scale encoded along with its nonce, then
+    /// prints it with their total. Fails if it would take the fees over the limit, otherwise they
+    /// are counted as paid.
+    async fn preview(
+        &mut self,
+        api: &OnlineClient<NodleConfig>,
+        transactions: &[(u64, Vec<u8>)],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut total = 0u128;
+        for (nonce, extrinsic) in transactions {
+            let fee = payment::query_info(api, extrinsic).await?.partial_fee;
+            let cost = fee.saturating_add(self.tip);
+            println!(
+                "nonce {nonce}: {} NODL ({} fee + {} tip)",
+                format_units(cost, TOKEN_DECIMALS),
+                format_units(fee, TOKEN_DECIMALS),
+                format_units(self.tip, TOKEN_DECIMALS)
+            );
+            total = total.saturating_add(cost);
+        }
+        println!(
+            "total cost of {} transaction(s): {} NODL",
+            transactions.len(),
+            format_units(total, TOKEN_DECIMALS)
+        );
+
+        let total = self.total.saturating_add(total);
+        if let Some(max_total) = self.max_total {
+            if total > max_total {
+                return Err(format!(
+                    "the transactions would cost {} NODL, over --max-total-fee {} NODL, so none \
+                     of them was sent",
+                    format_units(total, TOKEN_DECIMALS),
+                    format_units(max_total, TOKEN_DECIMALS)
+                )
+                .into());
+            }
+        }
+        self.total = total;
+        Ok(())
+    }
+}
+
+enum Mode {
+    /// Sign with the key and submit right away. The key is only loaded to sign the first
+    /// transaction.
+    Submit(Box<LazyKeypair>),
+    /// Add the transactions to an unsigned payload written to `path` by [`Sender::finish`].
+    Build {
+        path: PathBuf,
+        payload: UnsignedPayload,
+    },
+}
+
+/// The most times a transaction is sent before giving up on it.
+const MAX_ATTEMPTS: u32 = 3;
+
+/// A transaction sent by [`Sender::send_all`] and waiting to be finalized.
+struct InFlight<'a> {
+    /// The position of the call among those given to `send_all`.
+    index: usize,
+    call: &'a EncodedCall,
+    nonce: u64,
+    /// The number of the block the transaction is mortal from, if it is mortal.
+    checkpoint: Option<u64>,
+    extrinsic: Extrinsic,
+    tx_progress: TxProgress<NodleConfig, OnlineClient<NodleConfig>>,
+    attempts: u32,
+}
+
+/// What became of a call given to [`Sender::send_all`].
+enum Outcome {
+    NotSent,
+    /// Sent without waiting for it with `--wait-for none`.
+    Sent,
+    Executed(ExtrinsicEvents<NodleConfig>),
+    Failed(String),
+    /// Sent, but waiting in the pool behind a nonce that was never used.
+    Stuck(u64),
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Outcome::NotSent => write!(f, "not sent"),
+            Outcome::Sent => write!(f, "sent"),
+            Outcome::Executed(events) => write!(
+                f,
+                "executed {:?} in block {:?}",
+                events.extrinsic_hash(),
+                events.block_hash()
+            ),
+            Outcome::Failed(error) => write!(f, "failed: {error}"),
+            Outcome::Stuck(nonce) => write!(
+                f,
+                "waiting in the pool behind nonce {nonce}, it will be executed if another \
+                 transaction uses that nonce"
+            ),
+        }
+    }
+}
+
+impl Sender {
+    /// Creates a sender signing and submitting transactions with `signer`.
+    pub async fn new(
+        api: OnlineClient<NodleConfig>,
+        rpc: &LegacyRpcMethods<NodleConfig>,
+        args: TxArgs,
+        signer: LazyKeypair,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let account = signer.account_id();
+        let nonce = rpc.system_account_next_index(&account).await?;
+        Ok(Sender {
+            api,
+            rpc: rpc.clone(),
+            fees: Fees::new(&args)?,
+            args,
+            account,
+            nonce,
+            mode: Mode::Submit(Box::new(signer)),
+            multisig: None,
+        })
+    }
+
+    /// Creates a sender writing the transactions to be signed by `account` to `path`, without
+    /// needing its key.
+    pub async fn build(
+        api: OnlineClient<NodleConfig>,
+        rpc: &LegacyRpcMethods<NodleConfig>,
+        args: TxArgs,
+        account: AccountId32,
+        path: PathBuf,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let nonce = rpc.system_account_next_index(&account).await?;
+        let runtime_version = api.runtime_version();
+        let payload = UnsignedPayload {
+            genesis_hash: api.genesis_hash(),
+            spec_version: runtime_version.spec_version,
+            transaction_version: runtime_version.transaction_version,
+            account: account.clone(),
+            transactions: Vec::new(),
+        };
+        Ok(Sender {
+            api,
+            rpc: rpc.clone(),
+            fees: Fees::new(&args)?,
+            args,
+            account,
+            nonce,
+            mode: Mode::Build { path, payload },
+            multisig: None,
+        })
+    }
+
+    /// Sends every call on behalf of `multisig`, which the signer must be a signatory of.
+    pub fn set_multisig(&mut self, multisig: MultisigAccount) -> Result<(), String> {
+        multisig.other_signatories(&self.account)?;
+        println!("sending as multisig {multisig}");
+        self.multisig = Some(multisig);
+        Ok(())
+    }
+
+    /// The account signing the transactions.
+    pub fn account(&self) -> &AccountId32 {
+        &self.account
+    }
+
+    /// The account the calls are dispatched from, which is the multisig account when one is set.
+    pub fn origin(&self) -> AccountId32 {
+        match &self.multisig {
+            Some(multisig) => multisig.account_id(),
+            None => self.account.clone(),
+        }
+    }
+
+    /// The nonce of the next transaction.
+    pub fn nonce(&self) -> u64 {
+        self.nonce
+    }
+
+    /// Reads the latest finalized header, which mortal transactions are made valid from. There is
+    /// none for immortal transactions.
+    async fn checkpoint(&self) -> Result<Option<NodleHeader>, subxt::Error> {
+        if self.args.immortal {
+            return Ok(None);
+        }
+        let block = self.api.blocks().at_latest().await?;
+        Ok(Some(block.header().clone()))
+    }
+
+    /// Reads the best block and the nonce of the account in it. Unlike
+    /// `system_accountNextIndex`, it only counts the transactions included in the chain and not
+    /// those waiting in the pool.
+    async fn chain_nonce(&self) -> Result<(H256, u64), Box<dyn std::error::Error>> {
+        let best = self
+            .rpc
+            .chain_get_block_hash(None)
+            .await?
+            .ok_or("the node has no best block")?;
+        let account_query = eden::storage().system().account(&self.account);
+        let account = self
+            .api
+            .storage()
+            .at(best)
+            .fetch_or_default(&account_query)
+            .await?;
+        Ok((best, account.nonce.into()))
+    }
+
+    /// Reads the number of the block `hash`.
+    async fn block_number(&self, hash: H256) -> Result<u64, Box<dyn std::error::Error>> {
+        let header = self
+            .rpc
+            .chain_get_header(Some(hash))
+            .await?
+            .ok_or("the node does not know its best block")?;
+        Ok(header.number().into())
+    }
+
+    /// Signs and submits `calls` with consecutive nonces, no more than `--max-in-flight` at once,
+    /// then waits for them as `--wait-for` asks. The events of the executed calls are returned,
+    /// so there are none with `--wait-for none`. When building, they are added to the unsigned
+    /// payload instead and no events are returned either.
+    ///
+    /// With a multisig, each call is wrapped in `Multisig::as_multi` first. The fees of all the
+    /// transactions are estimated and printed before any of them is sent.
+    ///
+    /// Transactions that expire, are dropped by the pool or lose their nonce to another
+    /// transaction of the account are signed and sent again, with a new nonce in the last case.
+    /// The nonces left unused by transactions that fail for good are taken by the next ones
+    /// signed, so that those after them can still be included. If some transactions still fail,
+    /// what became of each call is printed before returning an error.
+    pub async fn send_all<Call: TxPayload>(
+        &mut self,
+        calls: &[Call],
+    ) -> Result<Vec<ExtrinsicEvents<NodleConfig>>, Box<dyn std::error::Error>> {
+        self.send_journaled(calls, &[], None).await
+    }
+
+    /// Same as [`Sender::send_all`], also recording in `journal` what becomes of each call, which
+    /// sends the unit at the same position in `units`.
+    pub async fn send_journaled<Call: TxPayload>(
+        &mut self,
+        calls: &[Call],
+        units: &[Unit],
+        mut journal: Option<&mut Journal>,
+    ) -> Result<Vec<ExtrinsicEvents<NodleConfig>>, Box<dyn std::error::Error>> {
+        if journal.is_some() {
+            assert_eq!(calls.len(), units.len(), "every call sends a unit");
+        }
+        let mut record = |index: usize, entry: &dyn Fn(Unit) -> Entry| match &mut journal {
+            Some(journal) => journal.record(entry(units[index].clone())),
+            None => Ok(()),
+        };
+
+        let mut encoded_calls = Vec::with_capacity(calls.len());
+        for call in calls {
+            let mut call = self.api.tx().call_data(call)?;
+            if let Some(multisig) = &self.multisig {
+                call = multisig.wrap(&self.api, &self.account, call).await?;
+            }
+            encoded_calls.push(EncodedCall(call));
+        }
+
+        let mut checkpoint = self.checkpoint().await?;
+        let mortality = self.args.mortality;
+        let tip = self.fees.tip;
+        let nonces = self.nonce..self.nonce + encoded_calls.len() as u64;
+
+        if let Mode::Build { payload, .. } = &mut self.mode {
+            if let Some(header) = &checkpoint {
+                println!(
+                    "the transactions must be submitted before block {}",
+                    mortality_end(header.number().into(), mortality)
+                );
+            }
+            let mut transactions = Vec::with_capacity(encoded_calls.len());
+            for (call, nonce) in encoded_calls.iter().zip(nonces) {
+                let params = params(nonce, checkpoint.as_ref(), mortality, tip);
+                transactions.push(UnsignedTransaction::new(&self.api, call, nonce, params)?);
+            }
+            let fee_transactions = transactions
+                .iter()
+                .map(|transaction| {
+                    (
+                        transaction.nonce,
+                        transaction.with_empty_signature(&self.account),
+                    )
+                })
+                .collect::<Vec<_>>();
+            self.fees.preview(&self.api, &fee_transactions).await?;
+
+            self.nonce += transactions.len() as u64;
+            payload.transactions.extend(transactions);
+            return Ok(Vec::new());
+        }
+        let Mode::Submit(signer) = &mut self.mode else {
+            unreachable!("built transactions are returned above");
+        };
+        if encoded_calls.is_empty() {
+            return Ok(Vec::new());
+        }
+        signer.load()?;
+        let Mode::Submit(signer) = &self.mode else {
+            unreachable!("built transactions are returned above");
+        };
+        let signer = signer.keypair();
+
+        let mut fee_transactions = Vec::with_capacity(encoded_calls.len());
+        for (call, nonce) in encoded_calls.iter().zip(nonces.clone()) {
+            let params = params(nonce, checkpoint.as_ref(), mortality, tip);
+            let extrinsic = self.api.tx().create_signed_offline(call, signer, params)?;
+            fee_transactions.push((nonce, extrinsic.encoded().to_vec()));
+        }
+        self.fees.preview(&self.api, &fee_transactions).await?;
+        self.nonce = nonces.end;
+        for index in 0..units.len() {
+            record(index, &|unit| Entry::new(unit, Status::Planned))?;
+        }
+
+        let total = encoded_calls.len();
+        let max_in_flight = self.args.max_in_flight as usize;
+        let wait_for = self.args.wait_for;
+        let mut outcomes = (0..total).map(|_| Outcome::NotSent).collect::<Vec<_>>();
+        let mut unsent = encoded_calls
+            .iter()
+            .zip(nonces)
+            .enumerate()
+            .collect::<VecDeque<_>>();
+        let mut in_flight = VecDeque::with_capacity(max_in_flight.min(total));
+        // The nonces of our transactions that failed for good, which the transactions after
+        // them wait for.
+        let mut free_nonces = BTreeSet::new();
+        loop {
+            // The transactions are signed right before being sent, since those waiting for room
+            // could otherwise expire before it.
+            if in_flight.len() < max_in_flight && !unsent.is_empty() {
+                checkpoint = self.checkpoint().await?;
+            }
+            while in_flight.len() < max_in_flight {
+                let Some((index, (call, nonce))) = unsent.pop_front() else {
+                    break;
+                };
+                let nonce = lowest_nonce(&mut free_nonces, nonce);
+                let params = params(nonce, checkpoint.as_ref(), mortality, tip);
+                let extrinsic = self.api.tx().create_signed_offline(call, signer, params)?;
+                let tx_hash = extrinsic.hash();
+                match extrinsic.submit_and_watch().await {
+                    Ok(tx_progress) => {
+                        record(index, &|unit| {
+                            Entry::sent(unit, Status::Sent, tx_hash, nonce)
+                        })?;
+                        in_flight.push_back(InFlight {
+                            index,
+                            call,
+                            nonce,
+                            checkpoint: checkpoint.as_ref().map(|header| header.number().into()),
+                            extrinsic,
+                            tx_progress,
+                            attempts: 1,
+                        });
+                    }
+                    // The next transactions cannot be included without this nonce.
+                    Err(e) => {
+                        record(index, &|unit| Entry {
+                            error: Some(e.to_string()),
+                            ..Entry::sent(unit, Status::Failed, tx_hash, nonce)
+                        })?;
+                        outcomes[index] = Outcome::Failed(e.to_string());
+                        free_nonces.insert(nonce);
+                    }
+                }
+            }
+            if wait_for == WaitFor::None && unsent.is_empty() {
+                for tx in in_flight.drain(..) {
+                    outcomes[tx.index] = match free_nonces.first() {
+                        Some(&free) if free < tx.nonce => Outcome::Stuck(free),
+                        _ => Outcome::Sent,
+                    };
+                }
+            }
+
+            let Some(tx) = in_flight.pop_front() else {
+                break;
+            };
+            let (in_block, result) = wait_for_inclusion(tx.tx_progress, wait_for).await;
+            let mut error = match result {
+                Ok(Some(events)) => {
+                    let status = match wait_for {
+                        WaitFor::Finalized => Status::Finalized,
+                        _ => Status::InBlock,
+                    };
+                    record(tx.index, &|unit| Entry {
+                        block_hash: Some(events.block_hash()),
+                        ..Entry::sent(unit, status, events.extrinsic_hash(), tx.nonce)
+                    })?;
+                    outcomes[tx.index] = Outcome::Executed(events);
+                    continue;
+                }
+                Ok(None) => {
+                    outcomes[tx.index] = Outcome::Sent;
+                    continue;
+                }
+                // The nonce is used even though the call failed, so the next transactions go on.
+                Err(e @ subxt::Error::Runtime(_)) => {
+                    record(tx.index, &|unit| Entry {
+                        error: Some(e.to_string()),
+                        ..Entry::sent(unit, Status::Failed, tx.extrinsic.hash(), tx.nonce)
+                    })?;
+                    outcomes[tx.index] = Outcome::Failed(e.to_string());
+                    continue;
+                }
+                Err(e) => e,
+            };
+
+            // The pool gave up on the transaction. Unless it made it to a block, in which case it
+            // may have been executed already, it is sent again if it only failed because it
+            // expired, its nonce was taken by another transaction of the account, or it was
+            // dropped while still valid.
+            let (best, chain_nonce) = self.chain_nonce().await?;
+            // An expired transaction fails validation with `BadProof` rather than
+            // `AncientBirthBlock` until its birth block is older than `BlockHashCount`, so expiry
+            // is told from the block numbers instead.
+            let best_number = self.block_number(best).await?;
+            let expired = tx
+                .checkpoint
+                .is_some_and(|checkpoint| best_number >= mortality_end(checkpoint, mortality));
+            let retry = !in_block
+                && tx.attempts < MAX_ATTEMPTS
+                && matches!(
+                    error,
+                    subxt::Error::Transaction(
+                        TransactionError::Invalid(_) | TransactionError::Dropped(_)
+                    )
+                )
+                && (expired
+                    || matches!(
+                        tx.extrinsic.validate_at(best).await?,
+                        ValidationResult::Valid(_)
+                            | ValidationResult::Invalid(
+                                TransactionInvalid::Stale | TransactionInvalid::Future
+                            )
+                    ));
+            free_nonces.retain(|&free| free >= chain_nonce);
+            if retry {
+                // A nonce taken by another transaction is replaced by a free one, or one after all
+                // of ours. A transaction waiting behind a free nonce takes it, which the pool only
+                // allows once it gave up on the transaction, so that it cannot run twice.
+                let nonce = if chain_nonce > tx.nonce {
+                    free_nonces.pop_first().unwrap_or_else(|| {
+                        let nonce = self.nonce.max(chain_nonce);
+                        self.nonce = nonce + 1;
+                        nonce
+                    })
+                } else {
+                    lowest_nonce(&mut free_nonces, tx.nonce)
+                };
+                println!(
+                    "transaction {}/{total} with nonce {}: {error}, sending it again with nonce \
+                     {nonce}",
+                    tx.index + 1,
+                    tx.nonce
+                );
+                checkpoint = self.checkpoint().await?;
+                let params = params(nonce, checkpoint.as_ref(), mortality, tip);
+                let extrinsic = self
+                    .api
+                    .tx()
+                    .create_signed_offline(tx.call, signer, params)?;
+                let tx_hash = extrinsic.hash();
+                match extrinsic.submit_and_watch().await {
+                    Ok(tx_progress) => {
+                        record(tx.index, &|unit| {
+                            Entry::sent(unit, Status::Sent, tx_hash, nonce)
+                        })?;
+                        let retried = InFlight {
+                            nonce,
+                            checkpoint: checkpoint.as_ref().map(|header| header.number().into()),
+                            extrinsic,
+                            tx_progress,
+                            attempts: tx.attempts + 1,
+                            ..tx
+                        };
+                        if nonce <= tx.nonce {
+                            in_flight.push_front(retried);
+                        } else {
+                            in_flight.push_back(retried);
+                        }
+                        continue;
+                    }
+                    Err(e) => {
+                        free_nonces.insert(nonce);
+                        error = e;
+                    }
+                }
+            }
+
+            record(tx.index, &|unit| Entry {
+                error: Some(error.to_string()),
+                ..Entry::sent(unit, Status::Failed, tx.extrinsic.hash(), tx.nonce)
+            })?;
+            outcomes[tx.index] = Outcome::Failed(error.to_string());
+            // The next transactions cannot be included until the nonce is used.
+            if chain_nonce <= tx.nonce {
+                free_nonces.insert(tx.nonce);
+            }
+        }
+        // Nonces left free would hold back the transactions sent next.
+        if !free_nonces.is_empty() {
+            self.nonce = self.rpc.system_account_next_index(&self.account).await?;
+        }
+
+        let succeeded = outcomes
+            .iter()
+            .filter(|outcome| matches!(outcome, Outcome::Executed(_) | Outcome::Sent))
+            .count();
+        if succeeded < total {
+            println!("{succeeded} of {total} transaction(s) went through:");
+            for (index, outcome) in outcomes.iter().enumerate() {
+                println!("    {}/{total}: {outcome}", index + 1);
+            }
+            return Err(format!("{} transaction(s) failed", total - succeeded).into());
+        }
+        Ok(outcomes
+            .into_iter()
+            .filter_map(|outcome| match outcome {
+                Outcome::Executed(events) => Some(events),
+                _ => None,
+            })
+            .collect())
+    }
+
+    /// Same as [`Sender::send_all`] for a single call.
+    pub async fn send<Call: TxPayload>(
+        &mut self,
+        call: &Call,
+    ) -> Result<Option<ExtrinsicEvents<NodleConfig>>, Box<dyn std::error::Error>> {
+        Ok(self.send_all(std::slice::from_ref(call)).await?.pop())
+    }
+
+    /// Writes the unsigned payload when building, so that it can be passed to `busypot sign`.
+    pub fn finish(self) -> Result<(), Box<dyn std::error::Error>> {
+        if let Mode::Build { path, payload } = self.mode {
+            if payload.transactions.is_empty() {
+                println!("no transaction to sign, {} was not written", path.display());
+                return Ok(());
+            }
+            offline::write_json(&path, &payload)?;
+            println!(
+                "wrote {} unsigned transaction(s) to {}",
+                payload.transactions.len(),
+                path.display()
+            );
+        }
+        Ok(())
+    }
+}
+
+/// The extrinsic parameters of the transaction with `nonce` and `tip`, mortal for `mortality`
+/// blocks from `checkpoint` if there is one.
+fn params(nonce: u64, checkpoint: Option<&NodleHeader>, mortality: u64, tip: u128) -> Params {
+    let builder = NodleExtrinsicParamsBuilder::default().nonce(nonce).tip(tip);
+    match checkpoint {
+        Some(header) => builder.mortal(header, mortality).build(),
+        None => builder.build(),
+    }
+}
+
+/// Takes the lowest of the `free` nonces instead of `nonce` if it comes first, freeing `nonce` in
+/// turn, so that the transactions signed next fill the nonces left unused.
+fn lowest_nonce(free: &mut BTreeSet<u64>, nonce: u64) -> u64 {
+    match free.first() {
+        Some(&lowest) if lowest < nonce => {
+            free.remove(&lowest);
+            free.insert(nonce);
+            lowest
+        }
+        _ => nonce,
+    }
+}
+
+/// The first block at which a transaction made mortal from block `checkpoint` for `period` blocks
+/// is no longer valid, following how `Era::mortal` rounds the period and quantizes its phase.
+fn mortality_end(checkpoint: u64, period: u64) -> u64 {
+    let period = period
+        .checked_next_power_of_two()
+        .unwrap_or(1 << 16)
+        .clamp(4, 1 << 16);
+    let quantize_factor = (period >> 12).max(1);
+    let phase = checkpoint % period / quantize_factor * quantize_factor;
+    let birth = (checkpoint.max(phase) - phase) / period * period + phase;
+    birth + period
+}
+
+/// Waits for a transaction to be included as far as `wait_for` asks, then returns its events
+/// once checked for success like `TxProgress::wait_for_finalized_success`. It also tells whether
+/// the transaction was ever in a block.
+///
+/// With [`WaitFor::None`], the transaction is only waited for until it is in a block, to make
+/// room for the next ones, and its events are not fetched.
+async fn wait_for_inclusion(
+    mut tx_progress: TxProgress<NodleConfig, OnlineClient<NodleConfig>>,
+    wait_for: WaitFor,
+) -> (
+    bool,
+    Result<Option<ExtrinsicEvents<NodleConfig>>, subxt::Error>,
+) {
+    let mut in_block = false;
+    while let Some(status) = tx_progress.next().await {
+        let error = match status {
+            Ok(TxStatus::InBestBlock(_)) if wait_for == WaitFor::Finalized => {
+                in_block = true;
+                continue;
+            }
+            Ok(TxStatus::InBestBlock(tx_in_block) | TxStatus::InFinalizedBlock(tx_in_block)) => {
+                let events = match wait_for {
+                    WaitFor::None => Ok(None),
+                    _ => tx_in_block.wait_for_success().await.map(Some),
+                };
+                return (true, events);
+            }
+            Ok(TxStatus::Error { message }) => TransactionError::Error(message).into(),
+            Ok(TxStatus::Invalid { message }) => TransactionError::Invalid(message).into(),
+            Ok(TxStatus::Dropped { message }) => TransactionError::Dropped(message).into(),
+            Ok(_) => continue,
+            Err(e) => e,
+        };
+        return (in_block, Err(error));
+    }
+    (in_block, Err(RpcError::SubscriptionDropped.into()))
+}
+
+/// Submits `extrinsics` in order, then waits for all of them to be finalized.
+pub async fn submit_all(
+    extrinsics: Vec<Extrinsic>,
+) -> Result<Vec<ExtrinsicEvents<NodleConfig>>, subxt::Error> {
+    let mut tx_progresses = VecDeque::new();
+    for extrinsic in extrinsics {
+        tx_progresses.push_back(extrinsic.submit_and_watch().await?);
+    }
+
+    let mut events = Vec::with_capacity(tx_progresses.len());
+    while let Some(tx_progress) = tx_progresses.pop_front() {
+        events.push(tx_progress.wait_for_finalized_success().await?);
+    }
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fills_the_lowest_free_nonce() {
+        let mut free = BTreeSet::from([5, 8]);
+        assert_eq!(lowest_nonce(&mut free, 7), 5);
+        assert_eq!(free, BTreeSet::from([7, 8]));
+        assert_eq!(lowest_nonce(&mut free, 3), 3);
+        assert_eq!(free, BTreeSet::from([7, 8]));
+    }
+
+    #[test]
+    fn mortality_ends_after_the_rounded_period() {
+        assert_eq!(mortality_end(100, 64), 164);
+        assert_eq!(mortality_end(100, 100), 228);
+        assert_eq!(mortality_end(0, 1), 4);
+        // Long periods quantize the phase, so the birth block may come before the checkpoint.
+        assert_eq!(mortality_end(100_007, 1 << 16), 165_536);
+    }
+}
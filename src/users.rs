@@ -0,0 +1,504 @@
+//! The users given to `register-users`, read from an address file or derived from a secret uri
+//! template
+
+use std::collections::HashMap;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use blake2::{Blake2b512, Digest};
+use serde::{Deserialize, Serialize};
+use subxt::utils::AccountId32;
+use subxt_signer::{sr25519, SecretUri};
+
+use crate::amount::TokenAmount;
+use crate::nodle::TOKEN_DECIMALS;
+use crate::offline;
+use crate::sponsorship::NewUser;
+
+/// Where `register-users` and `remove-users` take their users from.
+#[derive(Debug, clap::Args)]
+pub struct UserArgs {
+    /// The number of users with their addresses derived from `--user-template`
+    #[arg(short = 'n', long, default_value_t = 1)]
+    users: u32,
+    /// The starting id of the users. The ids will be incremented from this value.
+    /// The user address is dervied from `--user-template` with {i} replaced by the id
+    #[arg(short, long, default_value_t = 0)]
+    starting_id: u32,
+    /// The secret uri the users are derived from, where "{i}" stands for the id of each user
+    /// and "{pot}" for the pot id, such as "<phrase>//pot{pot}//user{i}".
+    ///
+    /// A "//" junction derives a hard key and a "/" junction a soft one. Soft keys can be
+    /// derived from the public key of their parent, so hard junctions keep users unlinkable.
+    #[arg(long, default_value = "//Alice/{i}", conflicts_with = "from_file")]
+    user_template: UserTemplate,
+    /// Takes the users listed in this file instead of deriving them.
+    ///
+    /// The users are given as SS58 addresses of the chain or hex public keys, one per line
+    /// optionally followed by their own fee and reserve quotas as "address,fee_quota,
+    /// reserve_quota", or in a `.json` file as an array of addresses or of objects with an
+    /// "address" and optionally a "fee_quota" and a "reserve_quota" given as strings. Users
+    /// listed more than once are only taken once, and their quotas only matter for
+    /// registering them.
+    #[arg(long, conflicts_with_all = ["users", "starting_id"])]
+    from_file: Option<PathBuf>,
+}
+
+/// Where `register-users` writes the users it derives.
+#[derive(Debug, clap::Args)]
+pub struct ExportArgs {
+    /// Writes the ids and addresses of the derived users to this JSON file, which
+    /// `--from-file` can read back.
+    #[arg(long, conflicts_with = "from_file")]
+    export: Option<PathBuf>,
+    /// Also writes the secret uri of each user to the `--export` file, which must not exist yet
+    /// and is created readable by the current user only.
+    #[arg(long, requires = "export")]
+    export_secrets: bool,
+}
+
+impl UserArgs {
+    /// Reads the users from `--from-file`, with addresses in `ss58_prefix`, or derives them for
+    /// the pot `pot_id`.
+    pub fn load(
+        &self,
+        pot_id: u32,
+        ss58_prefix: u16,
+    ) -> Result<Vec<User>, Box<dyn std::error::Error>> {
+        if let Some(path) = &self.from_file {
+            return read(path, ss58_prefix);
+        }
+        let ids = self.starting_id..self.users.saturating_add(self.starting_id);
+        Ok(derive(&self.user_template, pot_id, ids)?)
+    }
+}
+
+impl ExportArgs {
+    /// Writes `users`, derived by `user_args` for the pot `pot_id`, to `--export` if given, with
+    /// addresses in `ss58_prefix`.
+    pub fn export(
+        &self,
+        user_args: &UserArgs,
+        users: &[User],
+        pot_id: u32,
+        ss58_prefix: u16,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        match &self.export {
+            Some(path) => export(
+                path,
+                users,
+                &user_args.user_template,
+                pot_id,
+                ss58_prefix,
+                self.export_secrets,
+            ),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A user read from an address file or derived from a template, with the limits of its own
+/// given in the file, if any.
+pub struct User {
+    /// The id the user is known by in the run, such as the index its address is derived from.
+    pub id: u32,
+    pub account: AccountId32,
+    /// Where the user is in the address file, for reporting errors.
+    location: String,
+    fee_quota: Option<String>,
+    reserve_quota: Option<String>,
+}
+
+/// The limits given to the users that do not have their own, in the smallest unit of NODL.
+pub struct DefaultLimits {
+    pub fee_quota: u128,
+    pub reserve_quota: u128,
+}
+
+/// Resolves the limits of `users`, those without limits of their own getting `limits`.
+///
+/// Every user with invalid limits is printed before failing.
+pub fn with_limits(users: Vec<User>, limits: &DefaultLimits) -> Result<Vec<NewUser>, String> {
+    let mut new_users = Vec::with_capacity(users.len());
+    let mut invalid = 0;
+    for user in users {
+        let quota = |quota: Option<String>, default| match quota {
+            Some(quota) => TokenAmount::from_str(&quota)?.to_units(TOKEN_DECIMALS),
+            None => Ok(default),
+        };
+        let quotas = quota(user.fee_quota, limits.fee_quota).and_then(|fee_quota| {
+            Ok((fee_quota, quota(user.reserve_quota, limits.reserve_quota)?))
+        });
+        match quotas {
+            Ok((fee_quota, reserve_quota)) => new_users.push(NewUser {
+                id: user.id,
+                account: user.account,
+                fee_quota,
+                reserve_quota,
+            }),
+            Err(e) => {
+                println!("{}: {e}", user.location);
+                invalid += 1;
+            }
+        }
+    }
+    if invalid > 0 {
+        return Err(format!("{invalid} user(s) with invalid limits"));
+    }
+    Ok(new_users)
+}
+
+/// A user read from an address file, before its limits are resolved.
+struct Row {
+    /// Where the user is in the file, for reporting errors.
+    location: String,
+    address: String,
+    fee_quota: Option<String>,
+    reserve_quota: Option<String>,
+}
+
+/// A user as given in a JSON address file, either as its address alone or with its limits.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonUser {
+    Address(String),
+    WithLimits {
+        address: String,
+        #[serde(default)]
+        fee_quota: Option<String>,
+        #[serde(default)]
+        reserve_quota: Option<String>,
+    },
+}
+
+/// A secret uri with placeholders, "{i}" for the id of each user and "{pot}" for the pot they are
+/// registered in, from which the users are derived.
+#[derive(Debug, Clone)]
+pub struct UserTemplate(String);
+
+impl UserTemplate {
+    /// The secret uri of the user `id` of the pot `pot_id`.
+    fn secret_uri(&self, pot_id: u32, id: u32) -> String {
+        self.0
+            .replace("{i}", &id.to_string())
+            .replace("{pot}", &pot_id.to_string())
+    }
+}
+
+impl FromStr for UserTemplate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if !s.contains("{i}") {
+            return Err("the template must contain {i}, or every user would be the same".into());
+        }
+        let rest = s.replace("{i}", "").replace("{pot}", "");
+        if rest.contains('{') || rest.contains('}') {
+            return Err("the only placeholders of the template are {i} and {pot}".into());
+        }
+        Ok(UserTemplate(s.to_string()))
+    }
+}
+
+/// A derived user as written by [`export`].
+#[derive(Serialize)]
+struct ExportedUser {
+    id: u32,
+    address: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    secret_uri: Option<String>,
+}
+
+/// Derives the users with ids in `ids` for the pot `pot_id` from `template`.
+///
+/// Fails on the first user that cannot be derived, without printing its secret uri.
+fn derive(template: &UserTemplate, pot_id: u32, ids: Range<u32>) -> Result<Vec<User>, String> {
+    ids.map(|id| {
+        let secret_uri = SecretUri::from_str(&template.secret_uri(pot_id, id))
+            .map_err(|e| format!("user {id}: the template is not a valid secret uri: {e}"))?;
+        let keypair = sr25519::Keypair::from_uri(&secret_uri)
+            .map_err(|e| format!("user {id}: cannot derive the key from the template: {e}"))?;
+        Ok(User {
+            id,
+            account: keypair.public_key().into(),
+            location: format!("user {id}"),
+            fee_quota: None,
+            reserve_quota: None,
+        })
+    })
+    .collect()
+}
+
+/// Writes the ids and addresses with `ss58_prefix` of `users`, derived for the pot `pot_id` from
+/// `template`, to `path` as JSON, along with their secret uris if `with_secrets`. The file can be
+/// read back by [`read`].
+///
+/// With the secrets, the file must not exist yet and is created readable by the current user
+/// only.
+fn export(
+    path: &Path,
+    users: &[User],
+    template: &UserTemplate,
+    pot_id: u32,
+    ss58_prefix: u16,
+    with_secrets: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let exported = users
+        .iter()
+        .map(|user| ExportedUser {
+            id: user.id,
+            address: to_ss58(&user.account, ss58_prefix),
+            secret_uri: with_secrets.then(|| template.secret_uri(pot_id, user.id)),
+        })
+        .collect::<Vec<_>>();
+    if with_secrets {
+        // An existing file would keep its permissions, so the secrets only go to a new one that
+        // only the current user can read.
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let file = options.open(path).map_err(|e| {
+            format!(
+                "cannot create {} for the secrets of the users, which must not exist yet: {e}",
+                path.display()
+            )
+        })?;
+        serde_json::to_writer_pretty(file, &exported)?;
+    } else {
+        offline::write_json(path, &exported)?;
+    }
+    println!(
+        "wrote the addresses of {} user(s) to {}",
+        exported.len(),
+        path.display()
+    );
+    if with_secrets {
+        println!(
+            "{} holds the secrets of the users, keep it private",
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+/// Reads the users listed in the file at `path`, whose addresses must be SS58 addresses with
+/// `ss58_prefix` or hex public keys. The users are numbered from 0 in the order of the file, and
+/// those listed more than once are only kept the first time.
+///
+/// A `.json` file holds an array of addresses, or of objects with an `address` and optionally a
+/// `fee_quota` and a `reserve_quota` given as strings of NODL. Any other file has an address on
+/// each line, optionally followed by a fee quota and a reserve quota separated by commas, with an
+/// optional `address,fee_quota,reserve_quota` header. The limits are resolved by
+/// [`with_limits`].
+///
+/// Every invalid address is printed before failing.
+fn read(path: &Path, ss58_prefix: u16) -> Result<Vec<User>, Box<dyn std::error::Error>> {
+    let content = fs::read_to_string(path)?;
+    let rows = if path
+        .extension()
+        .is_some_and(|extension| extension == "json")
+    {
+        read_json(&content)?
+    } else {
+        read_lines(&content)
+    };
+
+    let mut users = Vec::with_capacity(rows.len());
+    let mut seen = HashMap::new();
+    let mut invalid = 0;
+    for row in rows {
+        match parse_address(&row.address, ss58_prefix) {
+            Ok(account) => {
+                if let Some(first) = seen.get(&account.0) {
+                    println!("{}: same user as {first}, skipped", row.location);
+                    continue;
+                }
+                seen.insert(account.0, row.location.clone());
+                users.push(User {
+                    id: users.len() as u32,
+                    account,
+                    location: row.location,
+                    fee_quota: row.fee_quota,
+                    reserve_quota: row.reserve_quota,
+                });
+            }
+            Err(e) => {
+                println!("{}: {e}", row.location);
+                invalid += 1;
+            }
+        }
+    }
+    if invalid > 0 {
+        return Err(format!("{invalid} invalid user(s) in {}", path.display()).into());
+    }
+    Ok(users)
+}
+
+fn read_json(content: &str) -> Result<Vec<Row>, serde_json::Error> {
+    let users: Vec<JsonUser> = serde_json::from_str(content)?;
+    Ok(users
+        .into_iter()
+        .enumerate()
+        .map(|(index, user)| {
+            let location = format!("user {index}");
+            match user {
+                JsonUser::Address(address) => Row {
+                    location,
+                    address,
+                    fee_quota: None,
+                    reserve_quota: None,
+                },
+                JsonUser::WithLimits {
+                    address,
+                    fee_quota,
+                    reserve_quota,
+                } => Row {
+                    location,
+                    address,
+                    fee_quota,
+                    reserve_quota,
+                },
+            }
+        })
+        .collect())
+}
+
+/// Reads a row from each line, skipping blank lines, `#` comments and the header.
+fn read_lines(content: &str) -> Vec<Row> {
+    let mut rows = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut fields = line.split(',').map(str::trim);
+        let address = fields.next().unwrap_or_default();
+        if rows.is_empty() && address.eq_ignore_ascii_case("address") {
+            continue;
+        }
+        let mut quota = || {
+            fields
+                .next()
+                .filter(|field| !field.is_empty())
+                .map(String::from)
+        };
+        rows.push(Row {
+            location: format!("line {}", index + 1),
+            address: address.to_string(),
+            fee_quota: quota(),
+            reserve_quota: quota(),
+        });
+    }
+    rows
+}
+
+/// Parses an SS58 address, which must have `ss58_prefix`, or a 0x prefixed hex public key.
+fn parse_address(address: &str, ss58_prefix: u16) -> Result<AccountId32, String> {
+    if let Some(hex) = address.strip_prefix("0x") {
+        let public_key: [u8; 32] = hex::decode(hex)
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(|| format!("`{address}` is not a 32 bytes public key"))?;
+        return Ok(public_key.into());
+    }
+
+    let account = AccountId32::from_str(address)
+        .map_err(|e| format!("`{address}` is not a valid address: {e}"))?;
+    let prefix = address_prefix(address)?;
+    if prefix != ss58_prefix {
+        return Err(format!(
+            "`{address}` is an address for SS58 prefix {prefix}, but the chain uses {ss58_prefix}"
+        ));
+    }
+    Ok(account)
+}
+
+/// Reads the SS58 prefix of an address whose checksum was already checked.
+fn address_prefix(address: &str) -> Result<u16, String> {
+    let data = bs58::decode(address)
+        .into_vec()
+        .map_err(|e| format!("`{address}` is not a valid address: {e}"))?;
+    match data[..] {
+        [first, ..] if first < 64 => Ok(first.into()),
+        // Two bytes prefixes spread their 14 bits over the first two bytes.
+        [first, second, ..] if first < 128 => {
+            let lower = (first << 2) | (second >> 6);
+            let upper = second & 0b0011_1111;
+            Ok(u16::from(lower) | u16::from(upper) << 8)
+        }
+        _ => Err(format!("`{address}` has an invalid SS58 prefix")),
+    }
+}
+
+/// Encodes `account` as an SS58 address with `ss58_prefix`.
+fn to_ss58(account: &AccountId32, ss58_prefix: u16) -> String {
+    let mut data = match ss58_prefix {
+        0..=63 => vec![ss58_prefix as u8],
+        _ => vec![
+            ((ss58_prefix & 0b1111_1100) >> 2) as u8 | 0b0100_0000,
+            (ss58_prefix >> 8) as u8 | ((ss58_prefix & 0b11) << 6) as u8,
+        ],
+    };
+    data.extend(account.0);
+    let checksum = Blake2b512::new()
+        .chain_update(b"SS58PRE")
+        .chain_update(&data)
+        .finalize();
+    data.extend(&checksum[..2]);
+    bs58::encode(data).into_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The public key of //Alice.
+    const ALICE: [u8; 32] = [
+        0xd4, 0x35, 0x93, 0xc7, 0x15, 0xfd, 0xd3, 0x1c, 0x61, 0x14, 0x1a, 0xbd, 0x04, 0xa9, 0x9f,
+        0xd6, 0x82, 0x2c, 0x85, 0x58, 0x85, 0x4c, 0xcd, 0xe3, 0x9a, 0x56, 0x84, 0xe7, 0xa5, 0x6d,
+        0xa2, 0x7d,
+    ];
+
+    #[test]
+    fn ss58_round_trips() {
+        let alice = AccountId32(ALICE);
+        for (prefix, address) in [
+            (37, "4mvedcXEAY9xEoEfdCDHviBJqgVQxW9DRwvs7yPsV1HtWU9k"),
+            (0, "15oF4uVJwmo4TdGW7VfQxNLavjCXviqxT9S1MgbjMNHr6Sp5"),
+            (42, "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY"),
+            (64, "cEaNSpz4PxFcZ7nT1VEKrKewH67rfx6MfcM6yKojyyPz7qaqp"),
+            (1000, "vji5kpxBaPKwct6PAdHiJUPCU1hqBEAPaLMF59sXAjn4NeEaJ"),
+            (16383, "yNa8JpqfFB3q8A29rCwSgxvdU94ufJw2yKKxDgznS5m1PoFvn"),
+        ] {
+            assert_eq!(to_ss58(&alice, prefix), address);
+            assert_eq!(address_prefix(address), Ok(prefix));
+            assert_eq!(parse_address(address, prefix), Ok(alice.clone()));
+        }
+    }
+
+    #[test]
+    fn parses_hex_public_keys() {
+        let hex_key = format!("0x{}", hex::encode(ALICE));
+        assert_eq!(parse_address(&hex_key, 37), Ok(AccountId32(ALICE)));
+    }
+
+    #[test]
+    fn rejects_addresses_of_other_chains() {
+        let error = parse_address("5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY", 37);
+        assert!(error.unwrap_err().contains("SS58 prefix 42"));
+    }
+
+    #[test]
+    fn rejects_malformed_hex_keys() {
+        for hex_key in [
+            "0xd43593c7",
+            "0xzz3593c715fdd31c61141abd04a99fd6822c8558854ccde39a5684e7a56da27d",
+        ] {
+            let error = parse_address(hex_key, 37).unwrap_err();
+            assert!(error.contains("is not a 32 bytes public key"), "{error}");
+        }
+    }
+}
@@ -0,0 +1,435 @@
+//! Helpers for composing the xcm messages sent by `propose-xcm`
+
+use std::fmt;
+use std::str::FromStr;
+
+use clap::ValueEnum;
+use subxt::OnlineClient;
+
+use crate::eden::{
+    self,
+    runtime_types::{
+        sp_weights::weight_v2::Weight,
+        xcm::{
+            double_encoded::DoubleEncoded, v2, v3, VersionedAssetId, VersionedMultiLocation,
+            VersionedXcm,
+        },
+    },
+};
+use crate::nodle::NodleConfig;
+
+/// Para id of Asset Hub on Polkadot and Kusama.
+const ASSET_HUB_PARA_ID: u32 = 1000;
+/// Para id of the Collectives chain on Polkadot.
+const COLLECTIVES_PARA_ID: u32 = 1001;
+/// Para id of Bridge Hub on Polkadot and Kusama.
+const BRIDGE_HUB_PARA_ID: u32 = 1002;
+
+/// The xcm version our runtime uses for encoding the keys of `PolkadotXcm::SupportedVersion`.
+const CURRENT_XCM_VERSION: u32 = 3;
+
+/// A location that is either a relay chain or one of its parachains, which covers every location
+/// used by `propose-xcm`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Location {
+    parents: u8,
+    parachain: Option<u32>,
+}
+
+impl Location {
+    fn v2(self) -> v2::multilocation::MultiLocation {
+        v2::multilocation::MultiLocation {
+            parents: self.parents,
+            interior: match self.parachain {
+                Some(para_id) => {
+                    v2::multilocation::Junctions::X1(v2::junction::Junction::Parachain(para_id))
+                }
+                None => v2::multilocation::Junctions::Here,
+            },
+        }
+    }
+
+    fn v3(self) -> v3::multilocation::MultiLocation {
+        v3::multilocation::MultiLocation {
+            parents: self.parents,
+            interior: match self.parachain {
+                Some(para_id) => {
+                    v3::junctions::Junctions::X1(v3::junction::Junction::Parachain(para_id))
+                }
+                None => v3::junctions::Junctions::Here,
+            },
+        }
+    }
+}
+
+/// The chain an xcm message is sent to, as seen from our parachain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Destination {
+    /// The relay chain our parachain is connected to.
+    Relay,
+    /// Another parachain connected to the same relay chain, such as a system parachain.
+    Sibling(u32),
+}
+
+impl Destination {
+    /// The location of the destination relative to our parachain.
+    fn location(&self) -> Location {
+        match *self {
+            Destination::Relay => Location {
+                parents: 1,
+                parachain: None,
+            },
+            Destination::Sibling(para_id) => Location {
+                parents: 1,
+                parachain: Some(para_id),
+            },
+        }
+    }
+
+    /// The number of hops from the destination up to the relay chain.
+    fn parents_to_relay(&self) -> u8 {
+        match self {
+            Destination::Relay => 0,
+            Destination::Sibling(_) => 1,
+        }
+    }
+
+    /// The location of the relay chain's native token relative to the destination.
+    fn relay_token(&self) -> Location {
+        Location {
+            parents: self.parents_to_relay(),
+            parachain: None,
+        }
+    }
+
+    /// The asset id of the relay chain's native token as seen from the destination.
+    pub fn relay_token_id(&self) -> VersionedAssetId {
+        VersionedAssetId::V3(v3::multiasset::AssetId::Concrete(self.relay_token().v3()))
+    }
+
+    /// The location of the parachain with `para_id` relative to the destination.
+    fn parachain(&self, para_id: u32) -> Location {
+        Location {
+            parents: self.parents_to_relay(),
+            parachain: Some(para_id),
+        }
+    }
+}
+
+impl FromStr for Destination {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "relay" => Ok(Destination::Relay),
+            "asset-hub" => Ok(Destination::Sibling(ASSET_HUB_PARA_ID)),
+            "collectives" => Ok(Destination::Sibling(COLLECTIVES_PARA_ID)),
+            "bridge-hub" => Ok(Destination::Sibling(BRIDGE_HUB_PARA_ID)),
+            _ => s
+                .strip_prefix("sibling:")
+                .and_then(|id| id.parse().ok())
+                .map(Destination::Sibling)
+                .ok_or_else(|| {
+                    format!(
+                        "invalid destination `{s}`, expected one of: relay, asset-hub, \
+                         collectives, bridge-hub or sibling:<para_id>"
+                    )
+                }),
+        }
+    }
+}
+
+impl fmt::Display for Destination {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Destination::Relay => write!(f, "relay"),
+            Destination::Sibling(para_id) => write!(f, "sibling:{para_id}"),
+        }
+    }
+}
+
+/// The weight required for executing the transact instruction on the destination.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransactWeight {
+    pub ref_time: u64,
+    pub proof_size: u64,
+}
+
+impl TransactWeight {
+    /// Increases both components of the weight by `percent` percent.
+    pub fn with_margin(self, percent: u64) -> Self {
+        let add_margin = |v: u64| v.saturating_add(v.saturating_mul(percent) / 100);
+        TransactWeight {
+            ref_time: add_margin(self.ref_time),
+            proof_size: add_margin(self.proof_size),
+        }
+    }
+}
+
+impl From<Weight> for TransactWeight {
+    fn from(weight: Weight) -> Self {
+        TransactWeight {
+            ref_time: weight.ref_time,
+            proof_size: weight.proof_size,
+        }
+    }
+}
+
+impl From<TransactWeight> for Weight {
+    fn from(weight: TransactWeight) -> Self {
+        Weight {
+            ref_time: weight.ref_time,
+            proof_size: weight.proof_size,
+        }
+    }
+}
+
+impl FromStr for TransactWeight {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (ref_time, proof_size) = s
+            .split_once(',')
+            .ok_or_else(|| format!("invalid weight `{s}`, expected ref_time,proof_size"))?;
+        Ok(TransactWeight {
+            ref_time: ref_time
+                .trim()
+                .parse()
+                .map_err(|e| format!("ref_time: {e}"))?,
+            proof_size: proof_size
+                .trim()
+                .parse()
+                .map_err(|e| format!("proof_size: {e}"))?,
+        })
+    }
+}
+
+impl fmt::Display for TransactWeight {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "ref_time: {}, proof_size: {}",
+            self.ref_time, self.proof_size
+        )
+    }
+}
+
+/// The xcm versions `propose-xcm` is able to build messages for, which are those of the
+/// `VersionedXcm` of our runtime metadata. It has no v4 types, so v4 destinations get v3.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum XcmVersion {
+    V2,
+    V3,
+}
+
+impl XcmVersion {
+    /// Picks the newest version we support that is not newer than `version`.
+    fn from_advertised(version: u32) -> Option<Self> {
+        match version {
+            0 | 1 => None,
+            2 => Some(XcmVersion::V2),
+            _ => Some(XcmVersion::V3),
+        }
+    }
+}
+
+impl fmt::Display for XcmVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            XcmVersion::V2 => write!(f, "v2"),
+            XcmVersion::V3 => write!(f, "v3"),
+        }
+    }
+}
+
+/// Finds the xcm version to use for `dest` based on what it advertised to our parachain, falling
+/// back to `PolkadotXcm::SafeXcmVersion` when the destination never did.
+pub async fn negotiate_version(
+    api: &OnlineClient<NodleConfig>,
+    dest: Destination,
+) -> Result<XcmVersion, Box<dyn std::error::Error>> {
+    let storage = api.storage().at_latest().await?;
+
+    let supported_version_query = eden::storage().polkadot_xcm().supported_version(
+        CURRENT_XCM_VERSION,
+        VersionedMultiLocation::V3(dest.location().v3()),
+    );
+    let advertised = match storage.fetch(&supported_version_query).await? {
+        Some(version) => version,
+        None => {
+            let safe_version_query = eden::storage().polkadot_xcm().safe_xcm_version();
+            storage.fetch(&safe_version_query).await?.ok_or(
+                "the xcm version of the destination is unknown, please provide --xcm-version",
+            )?
+        }
+    };
+
+    XcmVersion::from_advertised(advertised).ok_or_else(|| {
+        format!("xcm version {advertised} of the destination is not supported").into()
+    })
+}
+
+/// How several native transactions are combined in a single xcm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Batch {
+    /// Wrap the transactions in a single `Utility::batch_all`.
+    BatchAll,
+    /// Execute each transaction in its own transact instruction.
+    Instructions,
+}
+
+/// A native transaction executed by a transact instruction.
+#[derive(Clone)]
+pub struct TransactCall {
+    /// The scale encoded call dispatched on the destination.
+    pub call: Vec<u8>,
+    /// The weight reserved for executing `call`.
+    pub weight: TransactWeight,
+}
+
+/// An xcm program that pays for and executes native transactions on the destination, then
+/// deposits whatever is left of the fees back to the beneficiary parachain.
+#[derive(Clone)]
+pub struct TransactProgram {
+    /// The amount of the relay chain token withdrawn to pay for the execution.
+    pub fee: u128,
+    /// The transactions executed in order, each by its own transact instruction.
+    pub transacts: Vec<TransactCall>,
+    /// The para id receiving the surplus fees.
+    pub beneficiary_para: u32,
+}
+
+impl TransactProgram {
+    /// Builds the destination and the message to pass to `PolkadotXcm::send` in the given version.
+    pub fn build(
+        self,
+        dest: Destination,
+        version: XcmVersion,
+    ) -> (VersionedMultiLocation, VersionedXcm) {
+        match version {
+            XcmVersion::V2 => (
+                VersionedMultiLocation::V2(dest.location().v2()),
+                VersionedXcm::V2(self.build_v2(dest)),
+            ),
+            XcmVersion::V3 => (
+                VersionedMultiLocation::V3(dest.location().v3()),
+                VersionedXcm::V3(self.build_v3(dest)),
+            ),
+        }
+    }
+
+    fn build_v2(self, dest: Destination) -> v2::Xcm {
+        use v2::multiasset::{
+            AssetId, Fungibility, MultiAsset, MultiAssetFilter, MultiAssets, WildMultiAsset,
+        };
+        use v2::Instruction::{BuyExecution, DepositAsset, RefundSurplus, Transact, WithdrawAsset};
+
+        let fee_asset = || MultiAsset {
+            id: AssetId::Concrete(dest.relay_token().v2()),
+            fun: Fungibility::Fungible(self.fee),
+        };
+
+        let mut instructions = vec![
+            WithdrawAsset(MultiAssets(vec![fee_asset()])),
+            BuyExecution {
+                fees: fee_asset(),
+                weight_limit: v2::WeightLimit::Unlimited,
+            },
+        ];
+        instructions.extend(self.transacts.into_iter().map(|transact| Transact {
+            origin_type: v2::OriginKind::Native,
+            require_weight_at_most: transact.weight.ref_time,
+            call: DoubleEncoded {
+                encoded: transact.call,
+            },
+        }));
+        instructions.push(RefundSurplus);
+        instructions.push(DepositAsset {
+            assets: MultiAssetFilter::Wild(WildMultiAsset::All),
+            max_assets: 1,
+            beneficiary: dest.parachain(self.beneficiary_para).v2(),
+        });
+        v2::Xcm(instructions)
+    }
+
+    fn build_v3(self, dest: Destination) -> v3::Xcm {
+        use v3::multiasset::{
+            AssetId, Fungibility, MultiAsset, MultiAssetFilter, MultiAssets, WildMultiAsset,
+        };
+        use v3::Instruction::{BuyExecution, DepositAsset, RefundSurplus, Transact, WithdrawAsset};
+
+        let fee_asset = || MultiAsset {
+            id: AssetId::Concrete(dest.relay_token().v3()),
+            fun: Fungibility::Fungible(self.fee),
+        };
+
+        let mut instructions = vec![
+            WithdrawAsset(MultiAssets(vec![fee_asset()])),
+            BuyExecution {
+                fees: fee_asset(),
+                weight_limit: v3::WeightLimit::Unlimited,
+            },
+        ];
+        instructions.extend(self.transacts.into_iter().map(|transact| Transact {
+            origin_kind: v2::OriginKind::Native,
+            require_weight_at_most: transact.weight.into(),
+            call: DoubleEncoded {
+                encoded: transact.call,
+            },
+        }));
+        instructions.push(RefundSurplus);
+        instructions.push(DepositAsset {
+            assets: MultiAssetFilter::Wild(WildMultiAsset::All),
+            beneficiary: dest.parachain(self.beneficiary_para).v3(),
+        });
+        v3::Xcm(instructions)
+    }
+}
+
+/// Prints each instruction of `message` on its own line prefixed by `indent`, showing the
+/// transact payloads as hex.
+pub fn print_message(message: &VersionedXcm, indent: &str) {
+    let print_transact = |origin_kind: &v2::OriginKind, weight: String, call: &DoubleEncoded| {
+        println!(
+            "{indent}Transact {{ origin_kind: {origin_kind:?}, require_weight_at_most: {weight} }}"
+        );
+        println!("{indent}    call: 0x{}", hex::encode(&call.encoded));
+    };
+
+    match message {
+        VersionedXcm::V2(xcm) => {
+            println!("{indent}xcm v2:");
+            for instruction in &xcm.0 {
+                match instruction {
+                    v2::Instruction::Transact {
+                        origin_type,
+                        require_weight_at_most,
+                        call,
+                    } => print_transact(origin_type, require_weight_at_most.to_string(), call),
+                    other => println!("{indent}{other:?}"),
+                }
+            }
+        }
+        VersionedXcm::V3(xcm) => {
+            println!("{indent}xcm v3:");
+            for instruction in &xcm.0 {
+                match instruction {
+                    v3::Instruction::Transact {
+                        origin_kind,
+                        require_weight_at_most,
+                        call,
+                    } => print_transact(
+                        origin_kind,
+                        TransactWeight {
+                            ref_time: require_weight_at_most.ref_time,
+                            proof_size: require_weight_at_most.proof_size,
+                        }
+                        .to_string(),
+                        call,
+                    ),
+                    other => println!("{indent}{other:?}"),
+                }
+            }
+        }
+    }
+}